@@ -0,0 +1,142 @@
+//! Monster behaviour.
+//!
+//! Every turn, a monster first updates its `AiState` from what it can see, and then picks an
+//! action for that state. All of the randomness comes from the given `Rng`, so monsters behave
+//! the same way every time for the same seed.
+
+use crate::direction::{self, Direction};
+use crate::dungeon::Level;
+use crate::entity::{Ai, AiState, Entity, EntityId};
+use crate::path;
+use crate::player::Pos;
+use crate::rng::Rng;
+use crate::turn::ACTION_COST;
+
+/// Extra cost of a path going through another monster, which will probably have moved by the
+/// time the path gets there.
+const CROWD_COST: u32 = 4 * ACTION_COST;
+
+pub enum Action {
+    Wait,
+    Move(Pos),
+    Attack,
+}
+
+/// Decides what the monster does this turn. Returns its new state along with the action, or
+/// `None` if the entity has no AI.
+pub fn think(level: &Level, id: EntityId, player: Pos, sees_player: bool, rng: &mut Rng)
+    -> Option<(AiState, Action)>
+{
+    let me = level.entities.get(id)?;
+    let ai = me.ai?;
+    let state = next_state(&ai, me, player, sees_player);
+
+    let decision = match state {
+        AiState::Chase { last_seen } => {
+            if sees_player && direction::adjacent(me.pos, player) {
+                (state, Action::Attack)
+            } else {
+                match step_towards(level, me.pos, last_seen, player) {
+                    Some(action) => (state, action),
+                    // Can't get there, so give up.
+                    None => (AiState::Idle, Action::Wait),
+                }
+            }
+        },
+        AiState::Flee => match flee(level, me.pos, player) {
+            Some(pos) => (state, Action::Move(pos)),
+            // Cornered, fight back.
+            None if direction::adjacent(me.pos, player) => (state, Action::Attack),
+            None => (state, Action::Wait),
+        },
+        AiState::Idle => match ai.post {
+            Some(post) if post != me.pos => {
+                (state, step_towards(level, me.pos, post, player).unwrap_or(Action::Wait))
+            },
+            Some(_) => (state, Action::Wait),
+            None => (state, wander(level, me.pos, player, rng)),
+        },
+    };
+
+    Some(decision)
+}
+
+fn next_state(ai: &Ai, me: &Entity, player: Pos, sees_player: bool) -> AiState
+{
+    let hurt = me.stats.is_some_and(|s| s.hp * 100 <= s.max_hp * ai.flee_percent);
+
+    match ai.state {
+        _ if sees_player && hurt => AiState::Flee,
+        _ if sees_player => AiState::Chase { last_seen: player },
+        // Lost sight of the player, keep going to where they were seen last.
+        AiState::Chase { last_seen } if last_seen != me.pos => ai.state,
+        _ => AiState::Idle,
+    }
+}
+
+/// Whether a monster can step onto `pos`.
+fn is_free(level: &Level, pos: Pos, player: Pos) -> bool
+{
+    level.map.in_bounds(pos)
+        && level.map.cell(pos).props().passable
+        && pos != player
+        && level.entities.blocking_at(pos).is_none()
+}
+
+/// First step of the path to `to`, or `None` if there is no path.
+fn step_towards(level: &Level, from: Pos, to: Pos, player: Pos) -> Option<Action>
+{
+    let path = path::find(&level.map, from, to, |pos| {
+        let tile = level.map.cell(pos).props();
+        if !tile.passable {
+            return None;
+        }
+
+        let crowded = pos != to && level.entities.blocking_at(pos).is_some();
+
+        Some(tile.move_cost + if crowded { CROWD_COST } else { 0 })
+    })?;
+
+    let action = match path.first() {
+        Some(next) if is_free(level, *next, player) => Action::Move(*next),
+        _ => Action::Wait,
+    };
+
+    Some(action)
+}
+
+/// The neighbouring position that gets the monster farthest away from the player, or `None` if
+/// every step would get it closer.
+fn flee(level: &Level, from: Pos, player: Pos) -> Option<Pos>
+{
+    let distances = path::distances(&level.map, player, |pos| {
+        let tile = level.map.cell(pos).props();
+        if tile.passable { Some(tile.move_cost) } else { None }
+    });
+    let current = path::distance_at(&level.map, &distances, from).unwrap_or(0);
+
+    let mut best = None;
+    let mut farthest = current;
+
+    for dir in Direction::ALL {
+        if let Some(pos) = dir.step(from).filter(|pos| is_free(level, *pos, player)) {
+            let distance = path::distance_at(&level.map, &distances, pos).unwrap_or(0);
+            if distance > farthest {
+                best = Some(pos);
+                farthest = distance;
+            }
+        }
+    }
+
+    best
+}
+
+fn wander(level: &Level, from: Pos, player: Pos, rng: &mut Rng) -> Action
+{
+    let dir = Direction::ALL[rng.range(0, Direction::ALL.len() as u32) as usize];
+
+    match dir.step(from) {
+        Some(pos) if is_free(level, pos, player) => Action::Move(pos),
+        _ => Action::Wait,
+    }
+}
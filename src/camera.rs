@@ -0,0 +1,72 @@
+use crate::player::Pos;
+
+/// The part of a map that is visible in a frame of `height` x `width` cells.
+pub struct Camera {
+    /// Map position of the top-left corner of the view.
+    origin: Pos,
+    height: usize,
+    width: usize,
+}
+
+impl Camera {
+    pub fn new(height: usize, width: usize) -> Self
+    {
+        Self {
+            origin: Pos { y: 0, x: 0 },
+            height,
+            width,
+        }
+    }
+
+    pub fn height(&self) -> usize
+    {
+        self.height
+    }
+
+    pub fn width(&self) -> usize
+    {
+        self.width
+    }
+
+    /// Centres the view on `target`, without scrolling past the edges of the map. Maps smaller
+    /// than the view stay anchored at the top-left corner.
+    pub fn follow(&mut self, target: Pos, map_height: usize, map_width: usize)
+    {
+        self.origin.y = follow_axis(target.y as usize, self.height, map_height) as u32;
+        self.origin.x = follow_axis(target.x as usize, self.width, map_width) as u32;
+    }
+
+    /// Map position shown at view position `(y, x)`.
+    pub fn to_map(&self, y: usize, x: usize) -> Pos
+    {
+        Pos {
+            y: self.origin.y + y as u32,
+            x: self.origin.x + x as u32,
+        }
+    }
+
+    /// View position of a map position, if it's in view.
+    pub fn to_view(&self, pos: Pos) -> Option<(u32, u32)>
+    {
+        if pos.y < self.origin.y || pos.x < self.origin.x {
+            return None;
+        }
+
+        let y = pos.y - self.origin.y;
+        let x = pos.x - self.origin.x;
+        if y as usize >= self.height || x as usize >= self.width {
+            return None;
+        }
+
+        Some((y, x))
+    }
+}
+
+fn follow_axis(target: usize, view_len: usize, map_len: usize) -> usize
+{
+    if map_len <= view_len {
+        return 0;
+    }
+
+    target.saturating_sub(view_len / 2).min(map_len - view_len)
+}
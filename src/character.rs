@@ -0,0 +1,146 @@
+//! Character creation choices: race, class and attributes, and how they make up the starting
+//! character.
+
+use crate::combat::Stats;
+use crate::item;
+use crate::rng::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Race {
+    Human,
+    Elf,
+    Dwarf,
+    HalfOrc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Warrior,
+    Rogue,
+    Cleric,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attributes {
+    pub strength: i32,
+    pub dexterity: i32,
+    pub constitution: i32,
+}
+
+impl Race {
+    pub const ALL: [Race; 4] = [Race::Human, Race::Elf, Race::Dwarf, Race::HalfOrc];
+
+    pub fn name(self) -> &'static str
+    {
+        match self {
+            Race::Human => "Human",
+            Race::Elf => "Elf",
+            Race::Dwarf => "Dwarf",
+            Race::HalfOrc => "Half-orc",
+        }
+    }
+
+    /// Added to the rolled attributes.
+    pub fn modifiers(self) -> Attributes
+    {
+        let (strength, dexterity, constitution) = match self {
+            Race::Human => (0, 0, 0),
+            Race::Elf => (-1, 2, -1),
+            Race::Dwarf => (1, -1, 2),
+            Race::HalfOrc => (2, -1, 1),
+        };
+
+        Attributes { strength, dexterity, constitution }
+    }
+}
+
+impl Class {
+    pub const ALL: [Class; 3] = [Class::Warrior, Class::Rogue, Class::Cleric];
+
+    pub fn name(self) -> &'static str
+    {
+        match self {
+            Class::Warrior => "Warrior",
+            Class::Rogue => "Rogue",
+            Class::Cleric => "Cleric",
+        }
+    }
+
+    /// Stats before the attributes are taken into account.
+    fn base_stats(self) -> Stats
+    {
+        match self {
+            Class::Warrior => Stats::new(16, 4, 1, 70),
+            Class::Rogue => Stats::new(12, 3, 1, 80),
+            Class::Cleric => Stats::new(14, 3, 2, 70),
+        }
+    }
+
+    /// Ids of the items the class starts with. Weapons and armour start equipped.
+    pub fn starting_items(self) -> &'static [u32]
+    {
+        match self {
+            Class::Warrior => &[4, 5],
+            Class::Rogue => &[3, 7, 7],
+            Class::Cleric => &[6, 7],
+        }
+    }
+}
+
+/// Bonus of an attribute: 0 at 10 and 11, and 1 more for every 2 points above that.
+fn bonus(attribute: i32) -> i32
+{
+    (attribute - 10).div_euclid(2)
+}
+
+impl Attributes {
+    /// Rolls every attribute with 4d6, dropping the lowest die.
+    pub fn roll(rng: &mut Rng) -> Self
+    {
+        let mut roll = || {
+            let mut dice: Vec<i32> = (0..4).map(|_| rng.range(1, 7) as i32).collect();
+            dice.sort_unstable();
+            dice[1..].iter().sum()
+        };
+
+        Self {
+            strength: roll(),
+            dexterity: roll(),
+            constitution: roll(),
+        }
+    }
+
+    pub fn with_race(self, race: Race) -> Self
+    {
+        let m = race.modifiers();
+
+        Self {
+            strength: self.strength + m.strength,
+            dexterity: self.dexterity + m.dexterity,
+            constitution: self.constitution + m.constitution,
+        }
+    }
+
+    /// Starting stats of a character of `class` with these attributes.
+    pub fn stats(&self, class: Class) -> Stats
+    {
+        let base = class.base_stats();
+
+        Stats::new(
+            (base.max_hp + 2 * bonus(self.constitution)).max(5),
+            (base.attack + bonus(self.strength)).max(1),
+            (base.defense + bonus(self.dexterity) / 2).max(0),
+            base.accuracy + 3 * bonus(self.dexterity),
+        )
+    }
+}
+
+/// Names of the starting items of the class, for showing them before the character is made.
+pub fn starting_item_names(class: Class) -> Vec<String>
+{
+    class.starting_items()
+        .iter()
+        .filter_map(|id| item::by_id(*id))
+        .map(|i| i.name)
+        .collect()
+}
@@ -0,0 +1,182 @@
+//! Command-line arguments.
+
+use std::path::PathBuf;
+
+use crate::game::SLOT_COUNT;
+use crate::log::Level;
+
+pub const USAGE: &str = "\
+Usage: roggame [OPTIONS]
+
+Options:
+  -m, --map FILE     Map of the first level, and the map to edit (default: map.dat)
+  -s, --seed SEED    Seed of the dungeon of new games
+  -n, --no-splash    Skip the splash screen
+  -l, --load SLOT    Continue the game saved in SLOT (1-3)
+  -e, --editor       Start the map editor
+      --log FILE     Write a diagnostic log to FILE (default: $ROGGAME_LOG)
+      --log-level L  Lowest level that's logged: error, warning, info or debug
+                     (default: $ROGGAME_LOG_LEVEL, or info)
+  -V, --version      Print the version
+  -h, --help         Print this help";
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub map: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub skip_splash: bool,
+    /// Save slot to load, counted from 0.
+    pub load: Option<usize>,
+    pub editor: bool,
+    pub log: Option<PathBuf>,
+    pub log_level: Option<Level>,
+}
+
+#[derive(Debug)]
+pub enum Action {
+    Run(Options),
+    Help,
+    Version,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UsageError(String);
+
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Parses the arguments, not including the program name.
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Action, UsageError>
+{
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        // Long options can have their value after '='.
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        let is_flag = matches!(name, "--no-splash" | "--editor" | "--version" | "--help");
+        if inline_value.is_some() && is_flag {
+            return Err(UsageError(format!("{} doesn't take a value", name)));
+        }
+
+        let mut value = |what: &str| -> Result<String, UsageError> {
+            inline_value.clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| UsageError(format!("{} needs a {}", name, what)))
+        };
+
+        match name {
+            "-m" | "--map" => options.map = Some(PathBuf::from(value("file")?)),
+            "-s" | "--seed" => {
+                let seed = value("seed")?;
+                options.seed = Some(seed.parse()
+                    .map_err(|_| UsageError(format!("invalid seed: {}", seed)))?);
+            },
+            "-l" | "--load" => {
+                let slot = value("slot")?;
+                match slot.parse::<usize>() {
+                    Ok(n) if (1..=SLOT_COUNT).contains(&n) => options.load = Some(n - 1),
+                    _ => {
+                        return Err(UsageError(format!(
+                            "invalid slot: {} (expected 1 to {})",
+                            slot,
+                            SLOT_COUNT
+                        )));
+                    },
+                }
+            },
+            "--log" => options.log = Some(PathBuf::from(value("file")?)),
+            "--log-level" => {
+                let level = value("level")?;
+                options.log_level = Some(Level::from_name(&level)
+                    .ok_or_else(|| UsageError(format!("invalid log level: {}", level)))?);
+            },
+            "-n" | "--no-splash" => options.skip_splash = true,
+            "-e" | "--editor" => options.editor = true,
+            "-V" | "--version" => return Ok(Action::Version),
+            "-h" | "--help" => return Ok(Action::Help),
+            _ => return Err(UsageError(format!("unknown argument: {}", arg))),
+        }
+    }
+
+    if options.editor && options.load.is_some() {
+        return Err(UsageError("--editor and --load can't be used together".to_string()));
+    }
+
+    Ok(Action::Run(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> Result<Options, UsageError>
+    {
+        match parse(args.iter().map(|a| a.to_string()))? {
+            Action::Run(options) => Ok(options),
+            action => panic!("{:?} gave {:?}", args, action),
+        }
+    }
+
+    fn error(args: &[&str]) -> String
+    {
+        match parse(args.iter().map(|a| a.to_string())) {
+            Ok(action) => panic!("{:?} gave {:?}", args, action),
+            Err(UsageError(msg)) => msg,
+        }
+    }
+
+    #[test]
+    fn options()
+    {
+        let options = run(&["-m", "cellar.map", "--seed=42", "-n", "--load", "3"]).unwrap();
+
+        assert_eq!(options.map, Some(PathBuf::from("cellar.map")));
+        assert_eq!(options.seed, Some(42));
+        assert!(options.skip_splash);
+        assert_eq!(options.load, Some(2));
+        assert!(!options.editor);
+
+        let options = run(&["--editor", "--map=x.map", "--log", "game.log", "--log-level=debug"])
+            .unwrap();
+        assert!(options.editor);
+        assert_eq!(options.map, Some(PathBuf::from("x.map")));
+        assert_eq!(options.log, Some(PathBuf::from("game.log")));
+        assert_eq!(options.log_level, Some(Level::Debug));
+    }
+
+    #[test]
+    fn help_and_version()
+    {
+        assert!(matches!(parse(["-n".to_string(), "-h".to_string()]), Ok(Action::Help)));
+        assert!(matches!(parse(["--version".to_string()]), Ok(Action::Version)));
+    }
+
+    #[test]
+    fn errors()
+    {
+        assert_eq!(error(&["--seed=x"]), "invalid seed: x");
+        assert_eq!(error(&["-s"]), "-s needs a seed");
+        assert_eq!(error(&["--map"]), "--map needs a file");
+        assert_eq!(error(&["--load", "4"]), "invalid slot: 4 (expected 1 to 3)");
+        assert_eq!(error(&["--load=0"]), "invalid slot: 0 (expected 1 to 3)");
+        assert_eq!(
+            error(&["--editor", "--load", "1"]),
+            "--editor and --load can't be used together"
+        );
+        assert_eq!(error(&["--log-level", "loud"]), "invalid log level: loud");
+        assert_eq!(error(&["--editor=yes"]), "--editor doesn't take a value");
+        assert_eq!(error(&["--fly"]), "unknown argument: --fly");
+        assert_eq!(error(&["-x=1"]), "unknown argument: -x=1");
+    }
+}
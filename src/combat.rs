@@ -0,0 +1,70 @@
+//! Melee combat.
+
+use crate::rng::Rng;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    pub hp: i32,
+    pub max_hp: i32,
+    /// Maximum damage of a hit, before it's reduced by the defender's defense.
+    pub attack: i32,
+    pub defense: i32,
+    /// Chance to hit a defender with no defense, in percent.
+    pub accuracy: i32,
+}
+
+impl Stats {
+    pub const fn new(max_hp: i32, attack: i32, defense: i32, accuracy: i32) -> Self
+    {
+        Self {
+            hp: max_hp,
+            max_hp,
+            attack,
+            defense,
+            accuracy,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool
+    {
+        self.hp <= 0
+    }
+}
+
+/// Experience for killing something with these stats.
+pub fn experience(stats: &Stats) -> u32
+{
+    (stats.max_hp + stats.attack + stats.defense).max(1) as u32
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Attack {
+    Miss,
+    Hit {
+        damage: i32,
+        killed: bool,
+    },
+}
+
+/// Resolves one melee attack and applies the damage to the defender.
+///
+/// Every point of defense takes 5% off the chance to hit, but there's always at least a 5% chance
+/// to hit and to miss. A hit does between half and all of the attack, minus the defense, but
+/// always at least 1 damage.
+pub fn melee(attacker: &Stats, defender: &mut Stats, rng: &mut Rng) -> Attack
+{
+    let chance = (attacker.accuracy - defender.defense * 5).clamp(5, 95);
+    if !rng.chance(chance as u32) {
+        return Attack::Miss;
+    }
+
+    let attack = attacker.attack.max(0) as u32;
+    let roll = rng.range(attack / 2, attack + 1) as i32;
+    let damage = (roll - defender.defense).max(1);
+    defender.hp -= damage;
+
+    Attack::Hit {
+        damage,
+        killed: defender.is_dead(),
+    }
+}
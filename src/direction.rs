@@ -0,0 +1,64 @@
+//! The 8 directions that the player and monsters can move in.
+
+use crate::player::Pos;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Right,
+    Left,
+    Up,
+    Down,
+    TopRight,
+    TopLeft,
+    DownRight,
+    DownLeft,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 8] = [
+        Direction::Right,
+        Direction::Left,
+        Direction::Up,
+        Direction::Down,
+        Direction::TopRight,
+        Direction::TopLeft,
+        Direction::DownRight,
+        Direction::DownLeft,
+    ];
+
+    /// `(y, x)` offset of one step in this direction.
+    pub fn offset(self) -> (i32, i32)
+    {
+        match self {
+            Direction::Right => (0, 1),
+            Direction::Left => (0, -1),
+            Direction::Up => (-1, 0),
+            Direction::Down => (1, 0),
+            Direction::TopRight => (-1, 1),
+            Direction::TopLeft => (-1, -1),
+            Direction::DownRight => (1, 1),
+            Direction::DownLeft => (1, -1),
+        }
+    }
+
+    /// Position one step from `pos` in this direction, or `None` if that's off the top or the
+    /// left of the map. The other edges have to be checked against the map.
+    pub fn step(self, pos: Pos) -> Option<Pos>
+    {
+        let (dy, dx) = self.offset();
+        let y = pos.y as i64 + dy as i64;
+        let x = pos.x as i64 + dx as i64;
+
+        if y < 0 || x < 0 {
+            return None;
+        }
+
+        Some(Pos { y: y as u32, x: x as u32 })
+    }
+}
+
+/// Whether the positions are next to each other, diagonals included.
+pub fn adjacent(a: Pos, b: Pos) -> bool
+{
+    a != b && a.y.abs_diff(b.y) <= 1 && a.x.abs_diff(b.x) <= 1
+}
@@ -0,0 +1,217 @@
+//! A dungeon of several levels connected by staircases.
+
+use crate::map::{Map, Placement};
+use crate::mapgen;
+use crate::player::Pos;
+use crate::rng::Rng;
+use crate::tile;
+use crate::entity::{self, EntityStore};
+use crate::item;
+
+/// Number of levels in the dungeon.
+pub const DEPTH: usize = 10;
+
+const LEVEL_HEIGHT: usize = 40;
+const LEVEL_WIDTH: usize = 120;
+
+pub struct Level {
+    pub map: Map,
+    pub entities: EntityStore,
+}
+
+impl Level {
+    /// Creates the level's entities from the placements in the map.
+    pub fn new(map: Map) -> Self
+    {
+        let mut entities = EntityStore::new();
+
+        for p in &map.spawns {
+            if let Some(e) = entity::creature(p.id, p.pos) {
+                entities.spawn(e);
+            }
+        }
+        for p in &map.items {
+            if let Some(e) = entity::item(p.id, p.pos) {
+                entities.spawn(e);
+            }
+        }
+
+        Self { map, entities }
+    }
+}
+
+pub struct Dungeon {
+    seed: u64,
+    /// Levels that have been visited. Deeper levels are generated when they're first entered.
+    levels: Vec<Level>,
+    current: usize,
+}
+
+impl Dungeon {
+    /// Starts a dungeon. The first level is `first`, or a generated one if it's `None`.
+    pub fn new(seed: u64, first: Option<Map>) -> Self
+    {
+        let mut dungeon = Self {
+            seed,
+            levels: Vec::new(),
+            current: 0,
+        };
+
+        let first = match first {
+            Some(map) => map,
+            None => dungeon.generate_level(0),
+        };
+        dungeon.levels.push(Level::new(first));
+
+        dungeon
+    }
+
+    /// Restores a dungeon from its saved parts.
+    pub fn from_parts(seed: u64, levels: Vec<Level>, current: usize) -> Option<Self>
+    {
+        if current >= levels.len() || levels.len() > DEPTH {
+            return None;
+        }
+
+        Some(Self { seed, levels, current })
+    }
+
+    pub fn seed(&self) -> u64
+    {
+        self.seed
+    }
+
+    pub fn levels(&self) -> &[Level]
+    {
+        &self.levels
+    }
+
+    /// Index of the current level, 0 being the top one.
+    pub fn depth(&self) -> usize
+    {
+        self.current
+    }
+
+    pub fn level(&self) -> &Level
+    {
+        &self.levels[self.current]
+    }
+
+    pub fn level_mut(&mut self) -> &mut Level
+    {
+        &mut self.levels[self.current]
+    }
+
+    pub fn map(&self) -> &Map
+    {
+        &self.level().map
+    }
+
+    pub fn map_mut(&mut self) -> &mut Map
+    {
+        &mut self.level_mut().map
+    }
+
+    pub fn entities(&self) -> &EntityStore
+    {
+        &self.level().entities
+    }
+
+    pub fn entities_mut(&mut self) -> &mut EntityStore
+    {
+        &mut self.level_mut().entities
+    }
+
+    /// Goes one level down. Returns the position of the up staircase the player arrives on, or
+    /// `None` if this is the bottom level.
+    pub fn descend(&mut self) -> Option<Pos>
+    {
+        let next = self.current + 1;
+        if next >= DEPTH {
+            return None;
+        }
+
+        if next == self.levels.len() {
+            let level = self.generate_level(next);
+            self.levels.push(Level::new(level));
+        }
+        self.current = next;
+
+        let map = self.map();
+        Some(map.find(tile::STAIRS_UP).unwrap_or(map.meta.spawn))
+    }
+
+    /// Goes one level up. Returns the position of the down staircase the player arrives on, or
+    /// `None` if this is the top level.
+    pub fn ascend(&mut self) -> Option<Pos>
+    {
+        if self.current == 0 {
+            return None;
+        }
+        self.current -= 1;
+
+        let map = self.map();
+        Some(map.find(tile::STAIRS_DOWN).unwrap_or(map.meta.spawn))
+    }
+
+    /// Every level gets its own seed derived from the dungeon seed, so a level can be
+    /// regenerated without generating the ones above it.
+    pub fn level_seed(&self, depth: usize) -> u64
+    {
+        let mut rng = Rng::new(self.seed ^ (depth as u64).wrapping_mul(0x9e3779b97f4a7c15));
+        rng.next_u64()
+    }
+
+    fn generate_level(&self, depth: usize) -> Map
+    {
+        let seed = self.level_seed(depth);
+        let mut map = mapgen::generate(seed, LEVEL_HEIGHT, LEVEL_WIDTH);
+
+        if depth + 1 == DEPTH {
+            if let Some(pos) = map.find(tile::STAIRS_DOWN) {
+                map.set_cell(pos, tile::FLOOR);
+            }
+        }
+
+        populate(&mut map, &mut Rng::new(!seed), depth);
+
+        map
+    }
+}
+
+/// Places monsters and items on free floor cells of a generated level. Deeper levels get more
+/// and tougher monsters.
+fn populate(map: &mut Map, rng: &mut Rng, depth: usize)
+{
+    let monsters = 3 + depth as u32 * 2;
+    let items = 4;
+    // Monster ids are ordered by toughness, deeper levels unlock the tougher ones.
+    let tiers = (1 + depth / 3).min(entity::CREATURE_IDS.len());
+
+    let mut free: Vec<Pos> = (0..map.height() as u32)
+        .flat_map(|y| (0..map.width() as u32).map(move |x| Pos { y, x }))
+        .filter(|pos| map.cell(*pos) == tile::FLOOR && *pos != map.meta.spawn)
+        .collect();
+
+    let mut take_free = |rng: &mut Rng| -> Option<Pos> {
+        if free.is_empty() {
+            return None;
+        }
+        let i = rng.range(0, free.len() as u32) as usize;
+
+        Some(free.swap_remove(i))
+    };
+
+    for _ in 0..monsters {
+        if let Some(pos) = take_free(rng) {
+            let id = entity::CREATURE_IDS[rng.range(0, tiers as u32) as usize];
+            map.spawns.push(Placement { pos, id });
+        }
+    }
+    for _ in 0..items {
+        if let Some(pos) = take_free(rng) {
+            let id = item::IDS[rng.range(0, item::IDS.len() as u32) as usize];
+            map.items.push(Placement { pos, id });
+        }
+    }
+}
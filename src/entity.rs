@@ -0,0 +1,266 @@
+//! Things on the map other than terrain and the player: monsters, items and NPCs.
+//!
+//! An entity is a position and a glyph plus optional components. Behaviour is decided by which
+//! components an entity has, not by its kind, so e.g. anything with `stats` can be attacked.
+
+use crate::player::Pos;
+use crate::tile::RgbValue;
+use crate::turn::Actor;
+use crate::combat::Stats;
+use crate::item::{self, Item};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Monster,
+    Item,
+    Npc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AiState {
+    /// Wanders around, or stays at its post if it has one.
+    Idle,
+    /// Goes after the player, towards where it saw them last.
+    Chase {
+        last_seen: Pos,
+    },
+    /// Runs away from the player.
+    Flee,
+}
+
+/// Monster behaviour, see the `ai` module.
+#[derive(Debug, Clone, Copy)]
+pub struct Ai {
+    pub state: AiState,
+    /// Spot that the monster guards instead of wandering around. It returns there after losing
+    /// sight of the player.
+    pub post: Option<Pos>,
+    /// The monster flees when its HP drops to this percentage of the maximum or below.
+    pub flee_percent: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub kind: Kind,
+    pub name: String,
+    pub pos: Pos,
+    pub glyph: char,
+    pub color: RgbValue,
+    pub stats: Option<Stats>,
+    pub ai: Option<Ai>,
+    /// Anything that takes turns.
+    pub actor: Option<Actor>,
+    /// What an NPC says when bumped into.
+    pub dialogue: Option<String>,
+    /// The item lying on the floor, for entities of the `Item` kind.
+    pub item: Option<Item>,
+}
+
+impl Entity {
+    /// An item lying on the floor.
+    pub fn from_item(item: Item, pos: Pos) -> Self
+    {
+        Self {
+            kind: Kind::Item,
+            name: item.name.clone(),
+            pos,
+            glyph: item.glyph,
+            color: item.color,
+            stats: None,
+            ai: None,
+            actor: None,
+            dialogue: None,
+            item: Some(item),
+        }
+    }
+
+    /// Whether the entity occupies its cell, so that nothing else can move there.
+    pub fn blocks(&self) -> bool
+    {
+        self.kind != Kind::Item
+    }
+}
+
+/// Entities of one level. Ids stay valid until the entity is removed.
+#[derive(Default)]
+pub struct EntityStore {
+    entities: Vec<Option<Entity>>,
+}
+
+impl EntityStore {
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Restores a store with the given slots, keeping the ids of the entities.
+    pub fn from_slots(entities: Vec<Option<Entity>>) -> Self
+    {
+        Self { entities }
+    }
+
+    pub fn slots(&self) -> &[Option<Entity>]
+    {
+        &self.entities
+    }
+
+    pub fn spawn(&mut self, entity: Entity) -> EntityId
+    {
+        if let Some(i) = self.entities.iter().position(|e| e.is_none()) {
+            self.entities[i] = Some(entity);
+            return EntityId(i as u32);
+        }
+
+        self.entities.push(Some(entity));
+
+        EntityId(self.entities.len() as u32 - 1)
+    }
+
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity>
+    {
+        self.entities.get_mut(id.0 as usize)?.take()
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity>
+    {
+        self.entities.get(id.0 as usize)?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity>
+    {
+        self.entities.get_mut(id.0 as usize)?.as_mut()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &Entity)>
+    {
+        self.entities.iter()
+            .enumerate()
+            .filter_map(|(i, e)| Some((EntityId(i as u32), e.as_ref()?)))
+    }
+
+    /// The entity blocking `pos`, if any.
+    pub fn blocking_at(&self, pos: Pos) -> Option<EntityId>
+    {
+        self.iter()
+            .find(|(_, e)| e.pos == pos && e.blocks())
+            .map(|(id, _)| id)
+    }
+
+    pub fn items_at(&self, pos: Pos) -> impl Iterator<Item = (EntityId, &Entity)>
+    {
+        self.iter().filter(move |(_, e)| e.pos == pos && e.kind == Kind::Item)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Behaviour {
+    Wander,
+    /// Guards the spot where it's spawned.
+    Guard,
+}
+
+struct Template {
+    kind: Kind,
+    name: &'static str,
+    glyph: char,
+    color: RgbValue,
+    /// Combat stats, `None` for things that can't be attacked.
+    stats: Option<Stats>,
+    ai: Option<Behaviour>,
+    flee_percent: i32,
+    /// Speed of the actor, 0 for things that don't act.
+    speed: u32,
+    dialogue: Option<&'static str>,
+}
+
+/// Monsters and NPCs, by the id used in map spawn placements.
+static CREATURES: [(u32, Template); 4] = [
+    (1, Template {
+        kind: Kind::Monster,
+        name: "rat",
+        glyph: 'r',
+        color: (0xa0, 0x80, 0x60),
+        stats: Some(Stats::new(3, 2, 0, 60)),
+        ai: Some(Behaviour::Wander),
+        flee_percent: 50,
+        speed: 120,
+        dialogue: None,
+    }),
+    (2, Template {
+        kind: Kind::Monster,
+        name: "goblin",
+        glyph: 'g',
+        color: (0x60, 0xc0, 0x40),
+        stats: Some(Stats::new(6, 4, 1, 65)),
+        ai: Some(Behaviour::Wander),
+        flee_percent: 25,
+        speed: 100,
+        dialogue: None,
+    }),
+    (3, Template {
+        kind: Kind::Monster,
+        name: "orc",
+        glyph: 'o',
+        color: (0x40, 0x90, 0x30),
+        stats: Some(Stats::new(10, 6, 2, 70)),
+        ai: Some(Behaviour::Guard),
+        flee_percent: 0,
+        speed: 80,
+        dialogue: None,
+    }),
+    (100, Template {
+        kind: Kind::Npc,
+        name: "hermit",
+        glyph: 'h',
+        color: (0xd0, 0xd0, 0xff),
+        stats: Some(Stats::new(8, 2, 1, 50)),
+        ai: None,
+        flee_percent: 0,
+        speed: 100,
+        dialogue: Some("\"Turn back while you still can, stranger.\""),
+    }),
+];
+
+pub const CREATURE_IDS: [u32; 3] = [1, 2, 3];
+
+pub fn creature(id: u32, pos: Pos) -> Option<Entity>
+{
+    find_template(&CREATURES, id).map(|t| t.instantiate(pos))
+}
+
+pub fn item(id: u32, pos: Pos) -> Option<Entity>
+{
+    item::by_id(id).map(|i| Entity::from_item(i, pos))
+}
+
+fn find_template(templates: &'static [(u32, Template)], id: u32) -> Option<&'static Template>
+{
+    templates.iter()
+        .find(|(i, _)| *i == id)
+        .map(|(_, t)| t)
+}
+
+impl Template {
+    fn instantiate(&self, pos: Pos) -> Entity
+    {
+        Entity {
+            kind: self.kind,
+            name: self.name.to_string(),
+            pos,
+            glyph: self.glyph,
+            color: self.color,
+            stats: self.stats,
+            ai: self.ai.map(|behaviour| Ai {
+                state: AiState::Idle,
+                post: if behaviour == Behaviour::Guard { Some(pos) } else { None },
+                flee_percent: self.flee_percent,
+            }),
+            actor: if self.speed > 0 { Some(Actor::new(self.speed)) } else { None },
+            dialogue: self.dialogue.map(|d| d.to_string()),
+            item: None,
+        }
+    }
+}
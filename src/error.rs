@@ -0,0 +1,76 @@
+//! Errors that end up in front of the user, either in a dialog or, for the ones the game can't
+//! recover from, printed by `main` after the terminal is restored.
+
+use std::path::PathBuf;
+
+use crate::serial;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The size of the terminal couldn't be found out.
+    Terminal(std::io::Error),
+    /// The terminal is smaller than the game needs.
+    TerminalTooSmall {
+        height: usize,
+        width: usize,
+    },
+    /// Reading the terminal input failed.
+    Input(std::io::Error),
+    /// The terminal input was closed.
+    InputClosed,
+    /// A map file couldn't be loaded.
+    Map {
+        path: PathBuf,
+        source: serial::Error,
+    },
+    Save(serial::Error),
+    Load {
+        slot: usize,
+        source: serial::Error,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self {
+            Error::Terminal(e) => write!(f, "couldn't get the size of the terminal: {}", e),
+            Error::TerminalTooSmall { height, width } => {
+                write!(
+                    f,
+                    "the terminal is {}x{}, it needs to be at least {}x{}",
+                    height,
+                    width,
+                    crate::game::MIN_HEIGHT,
+                    crate::game::MIN_WIDTH
+                )
+            },
+            Error::Input(e) => write!(f, "couldn't read the input: {}", e),
+            Error::InputClosed => write!(f, "the input was closed"),
+            Error::Map { path, source } => {
+                write!(f, "couldn't load the map {}: {}", path.display(), source)
+            },
+            Error::Save(e) => write!(f, "couldn't save the game: {}", e),
+            Error::Load { slot, source } => {
+                write!(f, "couldn't load slot {}: {}", slot + 1, source)
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+    {
+        match self {
+            Error::Terminal(e) => Some(e),
+            Error::TerminalTooSmall { .. } => None,
+            Error::Input(e) => Some(e),
+            Error::InputClosed => None,
+            Error::Map { source, .. } => Some(source),
+            Error::Save(e) => Some(e),
+            Error::Load { source, .. } => Some(source),
+        }
+    }
+}
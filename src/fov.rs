@@ -0,0 +1,242 @@
+//! Field of view, using symmetric shadowcasting.
+//!
+//! See <https://www.albertford.com/shadowcasting/> for a description of the algorithm. Slopes
+//! are kept as fractions, so the result doesn't depend on floating point rounding.
+
+use crate::player::Pos;
+
+/// Calls `reveal` for every position visible from `origin` within `radius`.
+///
+/// `is_opaque` must return `true` for positions outside of the map, and `reveal` may be called
+/// with such positions (they're walls at the edge of the view), so it has to check the bounds.
+pub fn compute<F, G>(origin: Pos, radius: u32, is_opaque: F, mut reveal: G)
+where
+    F: Fn(i64, i64) -> bool,
+    G: FnMut(i64, i64),
+{
+    let origin = (origin.y as i64, origin.x as i64);
+    reveal(origin.0, origin.1);
+
+    let mut scanner = Scanner {
+        origin,
+        radius: radius as i64,
+        is_opaque: &is_opaque,
+        reveal: &mut reveal,
+    };
+
+    for quadrant in [Quadrant::North, Quadrant::East, Quadrant::South, Quadrant::West] {
+        scanner.scan(quadrant, Row {
+            depth: 1,
+            start: Slope { num: -1, den: 1 },
+            end: Slope { num: 1, den: 1 },
+        });
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Quadrant {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Quadrant {
+    /// Map position of the tile at `depth` rows away from `origin` and `col` columns off the
+    /// centre line of the quadrant.
+    fn transform(self, origin: (i64, i64), depth: i64, col: i64) -> (i64, i64)
+    {
+        let (y, x) = origin;
+
+        match self {
+            Quadrant::North => (y - depth, x + col),
+            Quadrant::South => (y + depth, x + col),
+            Quadrant::East => (y + col, x + depth),
+            Quadrant::West => (y + col, x - depth),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Slope {
+    num: i64,
+    den: i64,
+}
+
+#[derive(Clone, Copy)]
+struct Row {
+    depth: i64,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    fn min_col(&self) -> i64
+    {
+        // floor(depth * start + 1/2)
+        (2 * self.depth * self.start.num + self.start.den).div_euclid(2 * self.start.den)
+    }
+
+    fn max_col(&self) -> i64
+    {
+        // ceil(depth * end - 1/2)
+        -(-(2 * self.depth * self.end.num - self.end.den)).div_euclid(2 * self.end.den)
+    }
+
+    fn next(&self) -> Row
+    {
+        Row {
+            depth: self.depth + 1,
+            ..*self
+        }
+    }
+
+    fn is_symmetric(&self, col: i64) -> bool
+    {
+        col * self.start.den >= self.depth * self.start.num
+            && col * self.end.den <= self.depth * self.end.num
+    }
+}
+
+/// Slope of the left edge of the tile at `col`.
+fn slope(depth: i64, col: i64) -> Slope
+{
+    Slope {
+        num: 2 * col - 1,
+        den: 2 * depth,
+    }
+}
+
+struct Scanner<'a> {
+    origin: (i64, i64),
+    radius: i64,
+    is_opaque: &'a dyn Fn(i64, i64) -> bool,
+    reveal: &'a mut dyn FnMut(i64, i64),
+}
+
+impl Scanner<'_> {
+    fn scan(&mut self, quadrant: Quadrant, mut row: Row)
+    {
+        if row.depth > self.radius {
+            return;
+        }
+
+        let mut prev_opaque = None;
+
+        for col in row.min_col()..=row.max_col() {
+            let (y, x) = quadrant.transform(self.origin, row.depth, col);
+            let opaque = (self.is_opaque)(y, x);
+            let in_range = row.depth * row.depth + col * col <= self.radius * self.radius;
+
+            if in_range && (opaque || row.is_symmetric(col)) {
+                (self.reveal)(y, x);
+            }
+
+            if prev_opaque == Some(true) && !opaque {
+                row.start = slope(row.depth, col);
+            }
+            if prev_opaque == Some(false) && opaque {
+                let mut next = row.next();
+                next.end = slope(row.depth, col);
+                self.scan(quadrant, next);
+            }
+
+            prev_opaque = Some(opaque);
+        }
+
+        if prev_opaque == Some(false) {
+            self.scan(quadrant, row.next());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Which cells of `rows` are visible from `origin`. `#` is opaque, so is everything outside of
+    /// the rows.
+    fn visible(rows: &[&str], origin: Pos, radius: u32) -> Vec<Vec<bool>>
+    {
+        let height = rows.len() as i64;
+        let width = rows[0].len() as i64;
+        let in_bounds = |y: i64, x: i64| y >= 0 && x >= 0 && y < height && x < width;
+        let mut seen = vec![vec![false; width as usize]; height as usize];
+
+        compute(
+            origin,
+            radius,
+            |y, x| !in_bounds(y, x) || rows[y as usize].as_bytes()[x as usize] == b'#',
+            |y, x| {
+                if in_bounds(y, x) {
+                    seen[y as usize][x as usize] = true;
+                }
+            },
+        );
+
+        seen
+    }
+
+    #[test]
+    fn open_room()
+    {
+        let rows = ["#####", "#...#", "#...#", "#...#", "#####"];
+        let seen = visible(&rows, Pos { y: 2, x: 2 }, 8);
+
+        assert!(seen.iter().flatten().all(|v| *v));
+    }
+
+    #[test]
+    fn wall_hides_what_is_behind_it()
+    {
+        let rows = ["..........", "...#......", ".........."];
+        let seen = visible(&rows, Pos { y: 1, x: 0 }, 20);
+
+        assert!(seen[1][0]);
+        assert!(seen[1][3]);
+        assert!(seen[0][2]);
+        assert!(seen[2][2]);
+        assert!(seen[1][4..].iter().all(|v| !v));
+    }
+
+    #[test]
+    fn radius_limits_the_view()
+    {
+        let rows = [".........."];
+        let seen = visible(&rows, Pos { y: 0, x: 0 }, 4);
+
+        assert!(seen[0][4]);
+        assert!(!seen[0][5]);
+    }
+
+    #[test]
+    fn symmetric()
+    {
+        let rows = [
+            "..........",
+            "..#....#..",
+            "....#.....",
+            ".#......#.",
+            "......#...",
+            "..#.......",
+        ];
+        let floors: Vec<Pos> = (0..rows.len() as u32)
+            .flat_map(|y| (0..rows[0].len() as u32).map(move |x| Pos { y, x }))
+            .filter(|p| rows[p.y as usize].as_bytes()[p.x as usize] != b'#')
+            .collect();
+
+        let views: Vec<_> = floors.iter().map(|p| visible(&rows, *p, 20)).collect();
+
+        for (a, from_a) in floors.iter().zip(&views) {
+            for (b, from_b) in floors.iter().zip(&views) {
+                assert_eq!(
+                    from_a[b.y as usize][b.x as usize],
+                    from_b[a.y as usize][a.x as usize],
+                    "{:?} and {:?}",
+                    a,
+                    b,
+                );
+            }
+        }
+    }
+}
@@ -1,10 +1,14 @@
-use std::collections::HashMap;
-use cwinui::style::{Color, TextStyle};
+mod save;
+mod editor;
+
+pub use save::SLOT_COUNT;
+
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use cwinui::style::{Color, TextStyle, OwnedStyledText};
 use cwinui::style::WithStyle;
-use termion::input::TermRead;
 use termion::event::{Event, Key};
 
-use cwinui::screen::Screen;
 use cwinui::widget::{
     Widget,
     InteractiveWidget,
@@ -14,52 +18,102 @@ use cwinui::widget::{
 };
 use cwinui::layout::{
     Justify,
+    Align,
     Alignable,
 };
-use crate::input;
-use cwinui::pos;
-use crate::gameui::{StartMenu, CharacterCreationForm};
-use crate::player::Player;
+use crate::input::{self, Input};
+use crate::log;
+use crate::error::{Error, Result};
+use crate::serial;
+use crate::cli::Options;
+use crate::keymap::{self, Command, Keymap};
+use crate::gameui::{
+    StartMenu,
+    Form,
+    Field,
+    FormOutput,
+    FormValues,
+    Validator,
+    InventoryMenu,
+    InventoryAction,
+    ScrollView,
+    Terminal,
+};
+use crate::player::{Player, Pos, Condition};
+use crate::character::{self, Race, Class, Attributes};
 use crate::map::Map;
+use crate::tile::{self, RgbValue, TileProps, Bump};
+use crate::camera::Camera;
+use crate::fov;
+use crate::item;
+use crate::dungeon::Dungeon;
+use crate::entity::{Entity, EntityId, Kind};
+use crate::direction::Direction;
+use crate::ai::{self, Action};
+use crate::item::{CarryError, Effect, Entry, ItemKind, Slot};
+use crate::messages::{MessageLog, Severity};
+use crate::rng::Rng;
+use crate::turn::ACTION_COST;
+use crate::combat::{self, Attack};
 
-const HEIGHT: usize = 24;
-const WIDTH: usize  = 80;
-const MAP_HEIGHT: usize = HEIGHT - 4;
-const MAP_WIDTH: usize = WIDTH - 2;
+/// Smallest terminal the game runs in. Bigger ones give the map more room.
+pub const MIN_HEIGHT: usize = 24;
+pub const MIN_WIDTH: usize  = 80;
+/// Size of a new map in the editor.
+const MAP_HEIGHT: usize = MIN_HEIGHT - 4;
+const MAP_WIDTH: usize = MIN_WIDTH - 2;
 
-type RgbValue = (u8, u8, u8);
+const MAP_PATH: &str = "map.dat";
 
 const ACCENT_COLOR: RgbValue = (0x00, 0xd4, 0xaa);
+/// Width of the HUD on the right of the map, including its left edge.
+const SIDEBAR_WIDTH: usize = 22;
+
+const SIGHT_RADIUS: u32 = 10;
+/// Resting stops after this many turns even if nothing happens.
+const MAX_REST_TURNS: u64 = 500;
+
+/// Longest name a character can have.
+const MAX_NAME_LEN: usize = 16;
+/// Input errors in a row after which the game gives up on the terminal.
+const MAX_INPUT_ERRORS: u32 = 10;
 
 enum StartMenuOption {
     NewGame,
     LoadGame,
+    Editor,
     Exit,
 }
 
+fn time_seed() -> u64
+{
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
 struct Ui {
     window: Window,
     main_frame: Window,
+    /// Player stats, over the right side of the main frame. Only shown during the game.
+    sidebar: Window,
     bar: HorizBar,
     status_bar: Window,
 }
 
-pub struct Game {
-    screen: Screen,
-    ui: Ui,
-    state: GameState,
-}
-
-impl Game {
-    pub fn new() -> Self
+impl Ui {
+    /// Lays the UI out over a screen of `height` x `width` cells.
+    fn new(screen: &mut Terminal, height: usize, width: usize) -> Self
     {
-        let mut screen = Screen::init(HEIGHT, WIDTH);
-
-        let mut window =  Window::new(0, 0, HEIGHT, WIDTH);
-        let mut main_frame = Window::new(1, 1, HEIGHT - 2 - 2, WIDTH - 2);
-        let mut bar = HorizBar::new(HEIGHT as u32 - 1 - 2, 1, WIDTH - 2)
+        let mut window =  Window::new(0, 0, height, width);
+        let mut main_frame = Window::new(1, 1, height - 2 - 2, width - 2);
+        let mut bar = HorizBar::new(height as u32 - 1 - 2, 1, width - 2)
             .theme('#', '#', '#');
-        let mut status_bar = Window::new(HEIGHT as u32 - 1 - 1, 1, 1, WIDTH - 2);
+        let mut status_bar = Window::new(height as u32 - 1 - 1, 1, 1, width - 2);
+        let mut sidebar = Window::new(0, 0, main_frame.content_height(), SIDEBAR_WIDTH);
+        sidebar.align_to_inner(&main_frame, Align::TopRight);
+        sidebar.set_zindex(1);
 
         window.set_theme('#', '#', '#', '#', '#', '#', '#', '#');
         window.toggle_border().unwrap();
@@ -69,6 +123,7 @@ impl Game {
         screen.add_widget(&main_frame);
         screen.add_widget(&status_bar);
         screen.add_widget(&bar);
+        screen.add_widget(&sidebar);
 
         window.show();
         main_frame.show();
@@ -76,37 +131,293 @@ impl Game {
         bar.show();
 
         Self {
+            window,
+            main_frame,
+            sidebar,
+            bar,
+            status_bar,
+        }
+    }
+
+    /// Camera of the part of the main frame that the sidebar leaves for the map.
+    fn camera(&self) -> Camera
+    {
+        Camera::new(
+            self.main_frame.content_height(),
+            self.main_frame.content_width() - SIDEBAR_WIDTH,
+        )
+    }
+}
+
+/// `(height, width)` of the terminal.
+fn terminal_size() -> Result<(usize, usize)>
+{
+    let (width, height) = termion::terminal_size().map_err(Error::Terminal)?;
+
+    Ok((height as usize, width as usize))
+}
+
+pub struct Game {
+    screen: Terminal,
+    /// `(height, width)` the UI is laid out for.
+    size: (usize, usize),
+    ui: Ui,
+    /// What the status bar shows, as pieces of text and their colours, so that it can be drawn
+    /// again after a resize.
+    status_line: Vec<(String, Option<RgbValue>)>,
+    state: GameState,
+    camera: Camera,
+    /// Map file for the first level of a new game, and the one the editor works on.
+    map_path: PathBuf,
+    /// Seed of the dungeon of a new game.
+    seed: u64,
+    slot: usize,
+    keymap: Keymap,
+    /// Problems with the config file of the keymap, reported when the game starts.
+    keymap_problems: Vec<keymap::Problem>,
+}
+
+impl Game {
+    /// Takes over the terminal. Fails if it's smaller than `MIN_HEIGHT` x `MIN_WIDTH`.
+    pub fn new(options: &Options) -> Result<Self>
+    {
+        let (height, width) = terminal_size()?;
+        if height < MIN_HEIGHT || width < MIN_WIDTH {
+            return Err(Error::TerminalTooSmall { height, width });
+        }
+
+        let (keymap, keymap_problems) = Keymap::load();
+        let mut screen = Terminal::init(height, width);
+        let ui = Ui::new(&mut screen, height, width);
+
+        Ok(Self {
+            camera: ui.camera(),
             screen,
-            ui: Ui {
-                window,
-                main_frame,
-                bar,
-                status_bar,
+            size: (height, width),
+            ui,
+            status_line: Vec::new(),
+            state: GameState::new(Player::new(), Dungeon::new(0, Some(Map::new(0, 0)))),
+            map_path: options.map.clone().unwrap_or_else(|| PathBuf::from(MAP_PATH)),
+            seed: options.seed.unwrap_or_else(time_seed),
+            slot: 0,
+            keymap,
+            keymap_problems,
+        })
+    }
+
+    /// Lays the UI out again for the current size of the terminal. Below the minimum size, the
+    /// UI keeps the minimum size and the terminal cuts it off. Everything but the status bar has
+    /// to be redrawn afterwards, and widgets over the UI added again.
+    ///
+    /// Returns `false` if the UI already has that size, and nothing was done.
+    fn relayout(&mut self) -> bool
+    {
+        let (height, width) = match terminal_size() {
+            Ok(size) => size,
+            Err(e) => {
+                log::warning!("{}", e);
+                return false;
             },
-            state: GameState {
-                player: Player::new(),
-                map: Map::new(0, 0)
+        };
+        log::debug!("terminal resized to {}x{}", height, width);
+
+        let size = (height.max(MIN_HEIGHT), width.max(MIN_WIDTH));
+        if size == self.size {
+            return false;
+        }
+
+        self.size = size;
+        self.screen.resize(size.0, size.1);
+        self.ui = Ui::new(&mut self.screen, size.0, size.1);
+        self.camera = self.ui.camera();
+        self.draw_status();
+
+        true
+    }
+
+    /// Shows `overlay` over the main frame until it has an output. On a resize, the UI is laid
+    /// out again under the overlay, and the resize is passed on once the overlay is closed, for
+    /// whatever is under it to be redrawn.
+    fn run_overlay<W, O>(&mut self, overlay: &mut W) -> Result<O>
+    where
+        W: Widget + InteractiveWidget + OutputWidget<O> + Alignable
+    {
+        self.screen.add_widget(overlay);
+        overlay.align_centres(&self.ui.main_frame);
+        overlay.set_zindex(2);
+        overlay.show();
+
+        self.screen.refresh();
+
+        let mut resized = false;
+        let output = loop {
+            match input::next()? {
+                Input::Event(e) => overlay.process_event(e),
+                Input::Resize => {
+                    if self.relayout() {
+                        self.screen.add_widget(overlay);
+                        overlay.align_centres(&self.ui.main_frame);
+                        resized = true;
+                    }
+                },
+            }
+
+            self.screen.refresh();
+
+            if let Some(o) = overlay.try_get_output() {
+                break o;
+            }
+        };
+
+        self.screen.rm_widget(overlay);
+        self.screen.refresh();
+
+        if resized {
+            input::defer_resize();
+        }
+
+        Ok(output)
+    }
+
+    /// Waits for a key while `window` is shown over the main frame. Resizes are handled like in
+    /// `run_overlay`.
+    fn window_key(&mut self, window: &mut Window) -> Result<Key>
+    {
+        let mut resized = false;
+        let key = loop {
+            match input::next()? {
+                Input::Event(Event::Key(key)) => break key,
+                Input::Event(_) => (),
+                Input::Resize => {
+                    if self.relayout() {
+                        self.screen.add_widget(window);
+                        window.align_centres(&self.ui.main_frame);
+                        self.screen.refresh();
+                        resized = true;
+                    }
+                },
+            }
+        };
+
+        if resized {
+            input::defer_resize();
+        }
+
+        Ok(key)
+    }
+
+    /// Runs the game until the user exits. Errors that the user can do something about are shown
+    /// in a dialog, the rest are returned.
+    pub fn run(&mut self, options: &Options) -> Result<()>
+    {
+        if !options.skip_splash {
+            self.splash_screen()?;
+        }
+
+        if options.editor {
+            return self.editor();
+        }
+
+        if let Some(slot) = options.load {
+            match self.load_slot(slot) {
+                Ok(()) => match self.start()? {
+                    GameEnd::Quit => return Ok(()),
+                    GameEnd::Died => self.game_over()?,
+                },
+                Err(e) => self.error_dialog(&e)?,
+            }
+        }
+
+        loop {
+            let option = self.start_screen()?;
+
+            let ready = match option {
+                StartMenuOption::NewGame => match self.slot_select()? {
+                    Some(slot) => {
+                        self.slot = slot;
+                        self.character_create()? && self.new_game()?
+                    },
+                    None => false,
+                },
+                StartMenuOption::LoadGame => self.character_select()?,
+                StartMenuOption::Editor => {
+                    self.editor()?;
+                    false
+                },
+                StartMenuOption::Exit => return Ok(()),
+            };
+
+            if ready {
+                match self.start()? {
+                    GameEnd::Quit => return Ok(()),
+                    GameEnd::Died => self.game_over()?,
+                }
+            }
+        }
+    }
+
+    /// Starts a game with the created character. Returns `false` if it couldn't be started, after
+    /// telling the user why.
+    fn new_game(&mut self) -> Result<bool>
+    {
+        match self.init_map() {
+            Ok(()) => Ok(true),
+            Err(e) => {
+                self.error_dialog(&e)?;
+                Ok(false)
             },
         }
     }
 
-    pub fn run(&mut self)
+    /// Shows the error until the user presses a key.
+    fn error_dialog(&mut self, error: &Error) -> Result<()>
     {
-        self.splash_screen();
+        log::warning!("{}", error);
+
+        let width = self.ui.main_frame.content_width() - 4;
+        // Leave a margin of 2 on both sides.
+        let text_width = width - 4;
+        let mut lines: Vec<String> = Vec::new();
+        for word in error.to_string().split_whitespace() {
+            match lines.last_mut() {
+                Some(line) if line.chars().count() + 1 + word.chars().count() <= text_width => {
+                    line.push(' ');
+                    line.push_str(word);
+                },
+                _ => lines.push(word.to_string()),
+            }
+        }
 
-        let option = self.start_screen();
+        // Title, the message, an empty line and the hint.
+        let mut window = Window::new(0, 0, lines.len() + 3, width);
+        self.screen.add_widget(&window);
+        window.align_centres(&self.ui.main_frame);
+        window.set_zindex(3);
+        window.show();
 
-        match option {
-            StartMenuOption::NewGame => self.character_create(),
-            StartMenuOption::LoadGame => self.character_select(),
-            StartMenuOption::Exit => return,
+        window.printj(
+            "Error".with_style(|s|
+                s.fg_color(Color::Rgb(Severity::Danger.color()))
+                 .text_style(TextStyle::BOLD)
+            ),
+            Justify::HCentre(0)
+        );
+        for (i, line) in lines.iter().enumerate() {
+            window.printj(line.as_str(), Justify::HCentre(i as u32 + 1));
         }
+        window.printj("Press any key to continue.", Justify::BottomCentre);
+        self.screen.refresh();
+
+        self.window_key(&mut window)?;
+
+        self.screen.rm_widget(&window);
+        self.screen.refresh();
 
-        self.init_map();
-        self.start();
+        Ok(())
     }
 
-    fn splash_screen(&mut self) {
+    fn splash_screen(&mut self) -> Result<()>
+    {
         let mut logo = Window::new(0, 0, 6, self.ui.main_frame.content_width());
         let mut message = Window::new(0, 0, 1, self.ui.main_frame.content_width());
         self.screen.add_widget(&logo);
@@ -133,218 +444,1320 @@ impl Game {
         message.printj("Press any key to continue.", Justify::BottomCentre);
         self.screen.refresh();
 
-        input::getkey();
+        input::getkey()?;
 
         self.screen.rm_widget(&logo);
         self.screen.rm_widget(&message);
+
+        Ok(())
     }
 
-    fn start_screen(&mut self) -> StartMenuOption
+    fn start_screen(&mut self) -> Result<StartMenuOption>
     {
         let start_menu_opts = [
             "Start New Game",
             "Load Saved Game",
+            "Map Editor",
             "Exit",
         ];
 
         let mut menu = StartMenu::new(0, 0, None, None, &start_menu_opts);
-        self.screen.add_widget(&menu);
-        menu.align_centres(&self.ui.main_frame);
-        menu.set_zindex(2);
-        menu.show();
+        let output = self.run_overlay(&mut menu)?;
 
-        self.screen.refresh();
+        Ok(match output {
+            0 => StartMenuOption::NewGame,
+            1 => StartMenuOption::LoadGame,
+            2 => StartMenuOption::Editor,
+            3 => StartMenuOption::Exit,
+            _ => unreachable!("invalid start menu option."),
+        })
+    }
 
-        let mut output = None;
+    /// Asks for the player's name, race, class and attributes. Returns `false` if the user
+    /// cancelled.
+    fn character_create(&mut self) -> Result<bool>
+    {
+        let label = "Name".with_style(|f|
+            f.fg_color(Color::Rgb(ACCENT_COLOR))
+             .text_style(TextStyle::BOLD | TextStyle::UNDERLINE)
+        );
+        let fields = vec![
+            Field::text(label, "")
+                .with_validator(Validator::Required)
+                .with_validator(Validator::MaxLength(MAX_NAME_LEN))
+                .with_validator(Validator::AllowedChars(|c| {
+                    c.is_alphanumeric() || " -'".contains(c)
+                })),
+        ];
+        let form = Form::new(0, 0, 3, 29, fields)
+            .expect("the character creation form fits its fields");
 
-        for e in std::io::stdin().events() {
-            menu.process_event(e.unwrap());
+        self.set_status("enter: done  esc: back");
+        let name = match self.ask(form)? {
+            Some(values) => values.text("Name").trim().to_string(),
+            None => {
+                self.set_status("");
+                return Ok(false);
+            },
+        };
 
-            self.screen.refresh();
+        loop {
+            let races: Vec<&str> = Race::ALL.iter().map(|r| r.name()).collect();
+            let race = Race::ALL[self.choose("Choose your race.", &races)?];
+
+            let classes: Vec<&str> = Class::ALL.iter().map(|c| c.name()).collect();
+            let class = Class::ALL[self.choose("Choose your class.", &classes)?];
 
-            if let Some(o) = menu.try_get_output() {
-                output = Some(o);
+            if let Some(attributes) = self.roll_attributes(&name, race, class)? {
+                self.state.player = Player::create(name, race, class, attributes);
                 break;
             }
         }
 
-        self.screen.rm_widget(&menu);
+        self.set_status("");
 
-        match output {
-            Some(0) => StartMenuOption::NewGame,
-            Some(1) => StartMenuOption::LoadGame,
-            Some(2) => StartMenuOption::Exit,
-            _ => unreachable!("invalid start menu option."),
-        }
+        Ok(true)
     }
 
-    fn character_create(&mut self)
+    /// Lets the user pick one of the items from a menu. Returns the index of the item.
+    fn choose(&mut self, prompt: &str, items: &[&str]) -> Result<usize>
     {
-        let character_create_entries = [
-            "Name".with_style(|f|
-                f.fg_color(Color::Rgb(ACCENT_COLOR))
-                 .text_style(TextStyle::BOLD | TextStyle::UNDERLINE)
-            ),
-        ];
-        let mut form = CharacterCreationForm::new(0, 0, 4, 25, &character_create_entries);
-        self.screen.add_widget(&form);
-        form.align_centres(&self.ui.main_frame);
-        form.set_zindex(2);
-        form.show();
+        self.set_status(prompt);
 
-        self.screen.refresh();
+        let mut menu = StartMenu::new(0, 0, None, None, items);
+        let output = self.run_overlay(&mut menu)?;
+
+        Ok(output.min(items.len() - 1))
+    }
+
+    /// Shows the form until the user submits or cancels it. Returns `None` if they cancelled.
+    fn ask(&mut self, mut form: Form) -> Result<Option<FormValues>>
+    {
+        Ok(match self.run_overlay(&mut form)? {
+            FormOutput::Submit(values) => Some(values),
+            FormOutput::Cancel => None,
+        })
+    }
 
-        let mut output = HashMap::default();
+    /// Rolls the attributes, as many times as the user wants, and shows the character that they
+    /// make. Returns `None` if the user wants to choose the race and class again.
+    fn roll_attributes(
+        &mut self,
+        name: &str,
+        race: Race,
+        class: Class
+    ) -> Result<Option<Attributes>>
+    {
+        let mut rng = Rng::new(time_seed());
+        let equipment = character::starting_item_names(class).join(", ");
 
-        for e in std::io::stdin().events() {
-            form.process_event(e.unwrap());
+        self.set_status("r: re-roll  enter: accept  esc: back");
 
-            self.screen.refresh();
+        let mut window = Window::new(0, 0, 12, 50);
+        self.screen.add_widget(&window);
+        window.align_centres(&self.ui.main_frame);
+        window.set_zindex(2);
+        window.show();
 
-            if let Some(o) = form.try_get_output() {
-                output = o;
-                break;
+        let roll = |rng: &mut Rng| Attributes::roll(rng).with_race(race);
+        let mut attributes = roll(&mut rng);
+
+        let accepted = loop {
+            let stats = attributes.stats(class);
+
+            let lines = [
+                format!("{} the {} {}", name, race.name(), class.name()),
+                String::new(),
+                format!("Strength      {:>2}", attributes.strength),
+                format!("Dexterity     {:>2}", attributes.dexterity),
+                format!("Constitution  {:>2}", attributes.constitution),
+                String::new(),
+                format!("HP {}  Attack {}  Defense {}  Accuracy {}",
+                    stats.max_hp, stats.attack, stats.defense, stats.accuracy),
+                String::new(),
+                format!("Equipment: {}", equipment),
+            ];
+
+            for (i, line) in lines.iter().enumerate() {
+                let line = format!("{:^w$}", line, w = window.content_width());
+                window.print(i as u32 + 1, 0, line.as_str());
             }
-        }
+            self.screen.refresh();
 
-        self.state.player.name = output.remove("Name").unwrap();
-        self.state.player.pos.y = MAP_HEIGHT as u32 / 2;
-        self.state.player.pos.x = MAP_WIDTH as u32 / 2;
+            match self.window_key(&mut window)? {
+                Key::Char('r') => attributes = roll(&mut rng),
+                Key::Char('\n') => break Some(attributes),
+                Key::Esc => break None,
+                _ => (),
+            }
+        };
 
-        self.screen.rm_widget(&form);
+        self.screen.rm_widget(&window);
         self.screen.refresh();
+
+        Ok(accepted)
+    }
+
+    /// Lets the user pick a save slot. Returns `None` if they chose to go back.
+    fn slot_select(&mut self) -> Result<Option<usize>>
+    {
+        let mut items: Vec<String> = (0..save::SLOT_COUNT)
+            .map(|slot| match save::slot_summary(slot) {
+                Some(name) => format!("Slot {}: {}", slot + 1, name),
+                None => format!("Slot {}: <empty>", slot + 1),
+            })
+            .collect();
+        items.push("Back".to_string());
+        let items: Vec<&str> = items.iter().map(|i| i.as_str()).collect();
+
+        let mut menu = StartMenu::new(0, 0, None, None, &items);
+        let output = self.run_overlay(&mut menu)?;
+
+        // The last item is "Back".
+        Ok(Some(output).filter(|slot| *slot < save::SLOT_COUNT))
     }
 
-    fn character_select(&self) { todo!() } // TODO
+    /// Loads a saved game from a slot picked by the user. Returns `false` if they went back
+    /// without loading anything.
+    fn character_select(&mut self) -> Result<bool>
+    {
+        loop {
+            let slot = match self.slot_select()? {
+                Some(slot) => slot,
+                None => {
+                    self.set_status("");
+                    return Ok(false);
+                },
+            };
 
-    fn init_map(&mut self)
+            match self.load_slot(slot) {
+                Ok(()) => return Ok(true),
+                Err(e) => self.error_dialog(&e)?,
+            }
+        }
+    }
+
+    fn load_slot(&mut self, slot: usize) -> Result<()>
     {
-        let map_name = "map.dat";
-        let mut f = std::fs::File::open(map_name)
-            .expect(format!("Couldn't open the map: {}", map_name).as_str());
-        self.state.map = Map::from_reader(&mut f)
-            .expect(format!("Couldn't read the map: {}", map_name).as_str());
+        self.state = save::load(slot).map_err(|source| Error::Load { slot, source })?;
+        self.slot = slot;
+        log::info!("loaded slot {}: {}", slot + 1, self.state.player.name);
+        self.set_status("");
+
+        Ok(())
     }
 
-    fn start(&mut self) {
+    fn init_map(&mut self) -> Result<()>
+    {
+        // The first level is generated if there's no map file.
+        let first = match Some(&self.map_path).filter(|p| p.exists()) {
+            Some(path) => {
+                let map = std::fs::File::open(path)
+                    .map_err(serial::Error::from)
+                    .and_then(|mut f| Map::from_reader(&mut f));
+                let map = map.map_err(|source| Error::Map { path: path.clone(), source })?;
+                log::info!("loaded the map {}", path.display());
+                map.dump();
+                Some(map)
+            },
+            None => None,
+        };
+
+        // The character made in character creation.
+        let player = std::mem::replace(&mut self.state.player, Player::new());
+
+        self.state = GameState::new(player, Dungeon::new(self.seed, first));
+        self.state.player.pos = self.state.dungeon.map().meta.spawn;
+        log::info!("new game in slot {}, seed {}", self.slot + 1, self.seed);
+        self.log(format!("Welcome, {}! Dungeon seed: {}.", self.state.player.name, self.seed));
+        self.show_messages();
+
+        Ok(())
+    }
+
+    fn start(&mut self) -> Result<GameEnd>
+    {
+        for problem in std::mem::take(&mut self.keymap_problems) {
+            log::warning!("{}", problem);
+            self.log_as(Severity::Warning, problem.to_string());
+        }
+        self.show_messages();
+
+        self.ui.sidebar.show();
         self.update_map();
+        self.update_hud();
         self.screen.refresh();
 
-        let mut input = std::io::stdin().events();
+        let mut input_errors = 0;
 
         loop {
-            let next = input.next();
-
             // NOTE: for now, all of the operations, including redrawing and updating the game
             // state, are in lock-step with the user input. Nothing happens if there is no input.
-            // Therefore, all of the operations happen in the block where a new event has been
-            // received.
-
-            if let Some(Ok(event)) = next {
-                let mut recognised_event = true;
-
-                match event {
-                    Event::Key(Key::Right)
-                    | Event::Key(Key::Char('l')) => self.state.player_move(Direction::Right),
-                    Event::Key(Key::Left)
-                    | Event::Key(Key::Char('h')) => self.state.player_move(Direction::Left),
-                    Event::Key(Key::Up)
-                    | Event::Key(Key::Char('k')) => self.state.player_move(Direction::Up),
-                    Event::Key(Key::Down)
-                    | Event::Key(Key::Char('j')) => self.state.player_move(Direction::Down),
-                    Event::Key(Key::Char('y')) => self.state.player_move(Direction::TopLeft),
-                    Event::Key(Key::Char('u')) => self.state.player_move(Direction::TopRight),
-                    Event::Key(Key::Char('b')) => self.state.player_move(Direction::DownLeft),
-                    Event::Key(Key::Char('n')) => self.state.player_move(Direction::DownRight),
-                    Event::Key(Key::Char('q')) => break,
-                    _ => recognised_event = false,
+            // Therefore, all of the operations happen once a new event has been received.
+            let input = match input::next() {
+                Ok(input) => {
+                    input_errors = 0;
+                    input
+                },
+                // A read error can be a one-off, so it's skipped unless it keeps happening.
+                Err(e @ Error::Input(_)) if input_errors + 1 < MAX_INPUT_ERRORS => {
+                    log::error!("{}", e);
+                    input_errors += 1;
+                    continue;
+                },
+                // The game can't go on without input, but it doesn't have to be lost.
+                Err(e) => {
+                    if let Err(save_error) = self.save() {
+                        log::error!("{}", save_error);
+                    }
+                    return Err(e);
+                },
+            };
+            let event = match input {
+                Input::Event(event) => event,
+                Input::Resize => {
+                    self.relayout();
+                    self.ui.sidebar.show();
+                    self.update_map();
+                    self.update_hud();
+                    self.screen.refresh();
+                    continue;
+                },
+            };
+
+            let mut recognised_event = true;
+            let mut outcome = None;
+
+            let command = match event {
+                Event::Key(key) => self.keymap.command(key),
+                _ => None,
+            };
+
+            match command {
+                Some(Command::Move(direction)) => {
+                    outcome = Some(self.state.player_move(direction));
+                },
+                Some(Command::Wait) => self.state.player_wait(),
+                Some(Command::Pickup) => {
+                    let outcome = self.state.player_pickup();
+                    self.report_pickup(outcome);
+                },
+                Some(Command::Inventory) => self.inventory()?,
+                Some(Command::Messages) => self.message_history()?,
+                Some(Command::Rest) => {
+                    let outcome = self.state.player_rest();
+                    self.report_rest(outcome);
+                },
+                Some(Command::Descend) => {
+                    let outcome = self.state.player_descend();
+                    self.report_stairs(outcome);
+                },
+                Some(Command::Ascend) => {
+                    let outcome = self.state.player_ascend();
+                    self.report_stairs(outcome);
+                },
+                Some(Command::Quit) => match self.save() {
+                    Ok(()) => {
+                        self.ui.sidebar.hide();
+                        return Ok(GameEnd::Quit);
+                    },
+                    // Keep playing rather than lose the game.
+                    Err(e) => {
+                        self.error_dialog(&e)?;
+                        recognised_event = false;
+                    },
+                },
+                None => recognised_event = false,
+            }
+
+            if let Some(outcome) = outcome {
+                self.report_move(outcome);
+            }
+
+            if recognised_event {
+                self.state.advance();
+                if self.state.player.stats.is_dead() {
+                    self.ui.sidebar.hide();
+                    return Ok(GameEnd::Died);
                 }
 
-                if recognised_event {
-                    self.update_map();
+                self.show_messages();
+                self.update_map();
+                self.update_hud();
 
-                    self.screen.refresh();
+                self.screen.refresh();
+            }
+        }
+    }
+
+    fn log<S: Into<String>>(&mut self, msg: S)
+    {
+        self.state.log.log(msg);
+    }
+
+    fn log_as<S: Into<String>>(&mut self, severity: Severity, msg: S)
+    {
+        self.state.log.log_as(severity, msg);
+    }
+
+    /// Shows the messages logged since the last time in the status bar, each in the colour of its
+    /// severity. If they don't all fit, the latest ones are shown.
+    fn show_messages(&mut self)
+    {
+        let width = self.ui.status_bar.content_width();
+        let unread = self.state.log.unread();
+
+        let mut start = unread.len();
+        let mut len = 0;
+        while start > 0 {
+            let msg_len = unread[start - 1].text.chars().count() + if len > 0 { 1 } else { 0 };
+            if len + msg_len > width && len > 0 {
+                break;
+            }
+            len += msg_len;
+            start -= 1;
+        }
+
+        self.status_line = unread[start..].iter()
+            .map(|msg| (msg.text.clone(), Some(msg.severity.color())))
+            .collect();
+        self.draw_status();
+
+        self.state.log.mark_read();
+        self.screen.refresh();
+    }
+
+    /// Shows every message in the log.
+    fn message_history(&mut self) -> Result<()>
+    {
+        let lines: Vec<OwnedStyledText> = self.state.log.history()
+            .iter()
+            .map(|msg| {
+                let line = format!("{:>6}  {}", msg.turn, msg.text);
+                let color = msg.severity.color();
+                OwnedStyledText::from(line.as_str().with_style(|s| s.fg_color(Color::Rgb(color))))
+            })
+            .collect();
+
+        let mut view = ScrollView::new(
+            0,
+            0,
+            self.ui.main_frame.content_height(),
+            self.ui.main_frame.content_width(),
+            "Message history",
+            lines,
+        );
+        self.run_overlay(&mut view)
+    }
+
+    fn game_over(&mut self) -> Result<()>
+    {
+        // Death is permanent, the character can't be loaded again.
+        let _ = std::fs::remove_file(save::slot_path(self.slot));
+
+        let killer = self.state.killed_by.clone().unwrap_or_else(|| "something".to_string());
+        let lines = [
+            "You died.".to_string(),
+            String::new(),
+            format!("{} was killed by a {}", self.state.player.name, killer),
+            format!(
+                "on level {} after {} turns.",
+                self.state.dungeon.depth() + 1,
+                self.state.turn
+            ),
+        ];
+
+        self.clear_main_frame();
+        self.set_status("");
+
+        let mut window = Window::new(0, 0, lines.len() + 2, self.ui.main_frame.content_width());
+        self.screen.add_widget(&window);
+        window.align_centres(&self.ui.main_frame);
+        window.set_zindex(2);
+        window.show();
+
+        for (i, line) in lines.iter().enumerate() {
+            window.printj(line.as_str(), Justify::HCentre(i as u32));
+        }
+        window.printj("Press any key to continue.", Justify::BottomCentre);
+        self.screen.refresh();
+
+        self.window_key(&mut window)?;
+
+        self.screen.rm_widget(&window);
+        self.screen.refresh();
+
+        Ok(())
+    }
+
+    fn report_move(&mut self, outcome: MoveOutcome)
+    {
+        match outcome {
+            MoveOutcome::Moved => (),
+            MoveOutcome::PickedUp(outcome) => self.report_pickup(outcome),
+            MoveOutcome::Blocked(Some(tile)) => {
+                self.log(format!("There is a {} in the way. {}", tile.name, tile.description))
+            },
+            MoveOutcome::Blocked(None) => (),
+            MoveOutcome::OpenedDoor => self.log("You open the door."),
+            MoveOutcome::Attacked { target, result, level_up } => {
+                match result {
+                    Attack::Miss => self.log(format!("You miss the {}.", target)),
+                    Attack::Hit { killed: true, .. } => {
+                        self.log_as(Severity::Good, format!("You kill the {}.", target))
+                    },
+                    Attack::Hit { damage, .. } => {
+                        self.log(format!("You hit the {} for {}.", target, damage))
+                    },
                 }
-            } else if let Some(Err(_)) = next {
-                // TODO: log the error
+
+                if let Some(level) = level_up {
+                    self.log_as(Severity::Good, format!("Welcome to level {}!", level));
+                }
+            },
+            MoveOutcome::Interacted(msg) => self.log(msg),
+        }
+    }
+
+    fn report_stairs(&mut self, outcome: StairsOutcome)
+    {
+        match outcome {
+            StairsOutcome::Changed => {
+                let msg = format!("You arrive at level {}.", self.state.dungeon.depth() + 1);
+                self.log(msg);
+            },
+            StairsOutcome::NoStairs => self.log("There are no stairs here."),
+            StairsOutcome::Top => self.log("You can't leave the dungeon yet."),
+            StairsOutcome::Bottom => self.log("The stairs are blocked by rubble."),
+        }
+    }
+
+    fn report_pickup(&mut self, outcome: PickupOutcome)
+    {
+        match outcome {
+            PickupOutcome::NothingHere => self.log("There is nothing here."),
+            PickupOutcome::PickedUp { items, left } => {
+                if !items.is_empty() {
+                    self.log(format!("You pick up: {}.", items.join(", ")));
+                }
+                match left {
+                    Some((name, CarryError::Full)) => {
+                        let msg = format!("There is no room for the {}.", name);
+                        self.log_as(Severity::Warning, msg)
+                    },
+                    Some((name, CarryError::TooHeavy)) => {
+                        self.log_as(Severity::Warning, format!("The {} is too heavy.", name))
+                    },
+                    None => (),
+                }
+            },
+        }
+    }
+
+    /// Shows the inventory screen, and does whatever the player picks there.
+    fn inventory(&mut self) -> Result<()>
+    {
+        let inventory = &self.state.player.inventory;
+        let title = format!("Inventory, weight {}/{}", inventory.weight(), item::MAX_WEIGHT);
+        let entries = inventory.entries();
+        let labels: Vec<String> = entries.iter()
+            .map(|(entry, item)| match entry {
+                Entry::Equipped(Slot::Weapon) => format!("{} (wielded)", item.name),
+                Entry::Equipped(Slot::Armour) => format!("{} (worn)", item.name),
+                Entry::Pack(_) => item.name.clone(),
+            })
+            .collect();
+        let entries: Vec<Entry> = entries.into_iter().map(|(entry, _)| entry).collect();
+
+        let mut menu = InventoryMenu::new(0, 0, &title, &labels);
+        let outcome = match self.run_overlay(&mut menu)? {
+            InventoryAction::Use(i) => self.state.player_use_item(entries[i]),
+            InventoryAction::Drop(i) => self.state.player_drop(entries[i]),
+            InventoryAction::Close => return Ok(()),
+        };
+        self.report_item(outcome);
+
+        Ok(())
+    }
+
+    fn report_item(&mut self, outcome: ItemOutcome)
+    {
+        let (severity, msg) = match outcome {
+            ItemOutcome::Equipped(name) => (Severity::Info, format!("You equip the {}.", name)),
+            ItemOutcome::Unequipped(name) => {
+                (Severity::Info, format!("You take off the {}.", name))
+            },
+            ItemOutcome::PackFull => {
+                (Severity::Warning, "There is no room in your pack.".to_string())
+            },
+            ItemOutcome::Healed { item, hp } => {
+                (Severity::Good, format!("You use the {} and heal {} HP.", item, hp))
+            },
+            ItemOutcome::CantUse(name) => {
+                (Severity::Warning, format!("You can't use the {}.", name))
+            },
+            ItemOutcome::Dropped(name) => (Severity::Info, format!("You drop the {}.", name)),
+            ItemOutcome::Missing => return,
+        };
+        self.log_as(severity, msg);
+    }
+
+    fn report_rest(&mut self, outcome: RestOutcome)
+    {
+        match outcome {
+            RestOutcome::Rested(turns) => self.log(format!("You rest for {} turns.", turns)),
+            RestOutcome::NotSafe(name) => {
+                self.log_as(Severity::Warning, format!("You can't rest with the {} nearby.", name))
+            },
+            RestOutcome::Disturbed(name) => {
+                self.log_as(Severity::Warning, format!("You are disturbed by the {}.", name))
+            },
+            RestOutcome::Died => (),
+        }
+    }
+
+    fn save(&self) -> Result<()>
+    {
+        save::save(&self.state, self.slot).map_err(Error::Save)?;
+        log::info!("saved slot {}", self.slot + 1);
+
+        Ok(())
+    }
+
+    fn clear_main_frame(&mut self)
+    {
+        for y in 0..self.ui.main_frame.content_height() {
+            for x in 0..self.ui.main_frame.content_width() {
+                self.ui.main_frame.putc(y as u32, x as u32, ' ');
             }
         }
     }
 
+    fn set_status(&mut self, msg: &str)
+    {
+        self.status_line = vec![(msg.to_string(), None)];
+        self.draw_status();
+        self.screen.refresh();
+    }
+
+    fn draw_status(&mut self)
+    {
+        let width = self.ui.status_bar.content_width();
+        self.ui.status_bar.print(0, 0, " ".repeat(width).as_str());
+
+        let mut x = 0;
+        for (text, color) in &self.status_line {
+            let text: String = text.chars().take(width.saturating_sub(x)).collect();
+            match color {
+                Some(color) => {
+                    self.ui.status_bar.print(0, x as u32, text.as_str().with_style(|s|
+                        s.fg_color(Color::Rgb(*color))
+                    ));
+                },
+                None => self.ui.status_bar.print(0, x as u32, text.as_str()),
+            }
+            x += text.chars().count() + 1;
+        }
+    }
+
     fn update_map(&mut self)
     {
-        let map_height = self.state.map.height();
-        let map_width = self.state.map.width();
+        self.state.update_fov();
+
+        let map = self.state.dungeon.map();
+        self.camera.follow(self.state.player.pos, map.height(), map.width());
+
+        // Draw the visible part of the map itsef. Cells out of sight are drawn dimmed if the
+        // player has seen them before, and not at all otherwise.
+        for y in 0..self.camera.height() {
+            for x in 0..self.camera.width() {
+                let pos = self.camera.to_map(y, x);
 
-        // Draw the map itsef
-        for y in 0..map_height {
-            for x in 0..map_width {
-                let c = self.state.map.grid[pos!(map_width, y, x)].0 as char;
+                if map.in_bounds(pos) && (self.state.is_visible(pos) || map.is_explored(pos)) {
+                    let cell = map.cell(pos);
+                    let color = if self.state.is_visible(pos) {
+                        cell.props().color
+                    } else {
+                        dim(cell.props().color)
+                    };
 
-                self.ui.main_frame.putc(
-                    y as u32,
-                    x as u32,
-                    c,
-                )
+                    self.ui.main_frame.putc(
+                        y as u32,
+                        x as u32,
+                        cell.glyph().with_style(|s| s.fg_color(Color::Rgb(color))),
+                    )
+                } else {
+                    self.ui.main_frame.putc(y as u32, x as u32, ' ');
+                }
+            }
+        }
+
+        // Draw the entities in sight, items below everything else
+        let mut entities: Vec<&Entity> = self.state.dungeon.entities()
+            .iter()
+            .map(|(_, e)| e)
+            .filter(|e| self.state.is_visible(e.pos))
+            .collect();
+        entities.sort_by_key(|e| e.blocks());
+
+        for e in entities {
+            if let Some((y, x)) = self.camera.to_view(e.pos) {
+                let color = e.color;
+                self.ui.main_frame.putc(y, x, e.glyph.with_style(|s|
+                    s.fg_color(Color::Rgb(color))
+                ));
             }
         }
 
         // Draw the player
-        self.ui.main_frame.putc(
-            self.state.player.pos.y,
-            self.state.player.pos.x,
-            '@'.with_style(|s| s.fg_color(Color::Rgb(ACCENT_COLOR)))
-        );
+        if let Some((y, x)) = self.camera.to_view(self.state.player.pos) {
+            self.ui.main_frame.putc(
+                y,
+                x,
+                '@'.with_style(|s| s.fg_color(Color::Rgb(ACCENT_COLOR)))
+            );
+        }
     }
+
+    fn update_hud(&mut self)
+    {
+        let player = &self.state.player;
+        let stats = player.combat_stats();
+        let width = self.ui.sidebar.content_width();
+        let text_color = Color::Rgb((0xd0, 0xd0, 0xd0));
+
+        let mut lines: Vec<(String, Color)> = vec![
+            (String::new(), text_color),
+            (format!("{} {}", player.race.name(), player.class.name()), text_color),
+            (format!("HP {}/{}", stats.hp.max(0), stats.max_hp), text_color),
+        ];
+        // The HP bar goes on its own line, under the HP.
+        let bar_row = lines.len() as u32;
+        let xp = format!("Level {}  XP {}/{}", player.level, player.xp, player.xp_needed());
+        lines.extend([
+            (String::new(), text_color),
+            (xp, text_color),
+            (format!("Depth {}", self.state.dungeon.depth() + 1), text_color),
+            (format!("Turn {}", self.state.turn), text_color),
+            (String::new(), text_color),
+            (format!("Attack   {}", stats.attack), text_color),
+            (format!("Defense  {}", stats.defense), text_color),
+            (format!("Accuracy {}", stats.accuracy), text_color),
+            (String::new(), text_color),
+        ]);
+
+        let conditions = player.conditions();
+        if !conditions.is_empty() {
+            lines.push(("Effects:".to_string(), text_color));
+            for c in conditions {
+                lines.push((format!(" {}", c.name()), Color::Rgb(Severity::Warning.color())));
+            }
+        }
+
+        // The left edge of the sidebar separates it from the map.
+        for y in 0..self.ui.sidebar.content_height() {
+            let line = format!("#{:<w$}", "", w = width - 1);
+            self.ui.sidebar.print(y as u32, 0, line.as_str());
+        }
+
+        let name: String = player.name.chars().take(width - 2).collect();
+        self.ui.sidebar.print(0, 2, name.as_str().with_style(|s|
+            s.fg_color(Color::Rgb(ACCENT_COLOR))
+             .text_style(TextStyle::BOLD)
+        ));
+
+        for (y, (line, color)) in lines.iter().enumerate().skip(1) {
+            let line: String = line.chars().take(width - 2).collect();
+            self.ui.sidebar.print(y as u32, 2, line.as_str().with_style(|s| s.fg_color(*color)));
+        }
+
+        // HP bar, coloured by how much health is left.
+        let bar_width = width - 4;
+        let filled = stats.hp.max(0) as usize * bar_width / stats.max_hp.max(1) as usize;
+        let filled = filled.min(bar_width);
+        let bar_color = if stats.hp * 2 > stats.max_hp {
+            Severity::Good.color()
+        } else if stats.hp * 4 > stats.max_hp {
+            Severity::Warning.color()
+        } else {
+            Severity::Danger.color()
+        };
+
+        self.ui.sidebar.print(bar_row, 2, "[");
+        self.ui.sidebar.print(bar_row, 3, "=".repeat(filled).as_str().with_style(|s|
+            s.fg_color(Color::Rgb(bar_color))
+        ));
+        let empty = "-".repeat(bar_width - filled);
+        self.ui.sidebar.print(bar_row, 3 + filled as u32, empty.as_str().with_style(|s|
+            s.fg_color(Color::Rgb(dim(bar_color)))
+        ));
+        self.ui.sidebar.print(bar_row, 3 + bar_width as u32, "]");
+    }
+}
+
+/// Colour of remembered, but currently not visible, things.
+fn dim(color: RgbValue) -> RgbValue
+{
+    let (r, g, b) = color;
+    let grey = ((r as u32 + g as u32 + b as u32) / 3) as u8;
+
+    (grey / 3, grey / 3, grey / 2)
 }
 
-enum Direction {
-    Right,
-    Left,
-    Up,
-    Down,
-    TopRight,
-    TopLeft,
-    DownRight,
-    DownLeft,
+/// Result of a player move, for the main loop to react to.
+enum MoveOutcome {
+    Moved,
+    /// Blocked by a tile, or by the edge of the map.
+    Blocked(Option<&'static TileProps>),
+    OpenedDoor,
+    /// Stepped onto items and picked them up.
+    PickedUp(PickupOutcome),
+    Attacked {
+        target: String,
+        result: Attack,
+        /// The new level, if the player went up.
+        level_up: Option<u32>,
+    },
+    /// Bumped into something that reacted, e.g. a fountain or an NPC.
+    Interacted(String),
+}
+
+enum StairsOutcome {
+    Changed,
+    NoStairs,
+    /// Tried to go up from the top level.
+    Top,
+    /// Tried to go down from the bottom level.
+    Bottom,
+}
+
+enum PickupOutcome {
+    NothingHere,
+    /// Picked up the named items, and stopped at the first item that didn't fit, if any.
+    PickedUp {
+        items: Vec<String>,
+        left: Option<(String, CarryError)>,
+    },
+}
+
+enum ItemOutcome {
+    Equipped(String),
+    Unequipped(String),
+    /// No room in the pack to unequip an item.
+    PackFull,
+    Healed {
+        item: String,
+        hp: i32,
+    },
+    CantUse(String),
+    Dropped(String),
+    /// The inventory entry doesn't exist.
+    Missing,
+}
+
+enum RestOutcome {
+    /// Rested for the given number of turns without being disturbed.
+    Rested(u64),
+    /// Didn't rest at all, because a monster is in sight.
+    NotSafe(String),
+    /// A monster came into sight.
+    Disturbed(String),
+    /// Killed in sleep.
+    Died,
+}
+
+enum GameEnd {
+    /// Saved and quit.
+    Quit,
+    Died,
+}
+
+/// Who acts next.
+enum Turn {
+    Player,
+    Entity(EntityId),
 }
 
 struct GameState {
     player: Player,
-    map: Map,
+    dungeon: Dungeon,
+    /// Cells currently in the player's field of view.
+    visible: Vec<bool>,
+    /// Number of ticks since the start of the game. A tick is one turn at normal speed.
+    turn: u64,
+    /// Randomness of everything that happens during the game, as opposed to generating levels.
+    rng: Rng,
+    log: MessageLog,
+    /// Name of whatever killed the player.
+    killed_by: Option<String>,
 }
 
 impl GameState {
+    pub fn new(player: Player, dungeon: Dungeon) -> Self
+    {
+        let rng = Rng::new(!dungeon.seed());
+
+        Self {
+            player,
+            dungeon,
+            visible: Vec::new(),
+            turn: 0,
+            rng,
+            log: MessageLog::new(),
+            killed_by: None,
+        }
+    }
+
+    /// Recomputes the player's field of view and marks everything in it as explored.
+    pub fn update_fov(&mut self)
+    {
+        let map = self.dungeon.map_mut();
+        let width = map.width();
+        let mut visible = vec![false; map.height() * width];
+
+        let to_pos = |y: i64, x: i64| -> Option<Pos> {
+            if y < 0 || x < 0 {
+                return None;
+            }
+            let pos = Pos { y: y as u32, x: x as u32 };
+
+            if map.in_bounds(pos) { Some(pos) } else { None }
+        };
+        let is_opaque = |y, x| match to_pos(y, x) {
+            Some(pos) => map.cell(pos).props().opaque,
+            None => true,
+        };
+        let mut seen = Vec::new();
+
+        fov::compute(self.player.pos, SIGHT_RADIUS, is_opaque, |y, x| {
+            if let Some(pos) = to_pos(y, x) {
+                seen.push(pos);
+            }
+        });
+
+        for pos in seen {
+            visible[pos.y as usize * width + pos.x as usize] = true;
+            map.set_explored(pos);
+        }
+
+        self.visible = visible;
+    }
+
+    pub fn is_visible(&self, pos: Pos) -> bool
+    {
+        self.visible.get(pos.y as usize * self.dungeon.map().width() + pos.x as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Lets the world run until the player can act again, after the player's action spent
+    /// some energy. Stops early if the player dies.
+    pub fn advance(&mut self)
+    {
+        // Monsters see the player only if the player sees them.
+        self.update_fov();
+
+        while !self.player.stats.is_dead() {
+            match self.next_turn() {
+                Some(Turn::Player) => return,
+                Some(Turn::Entity(id)) => self.entity_act(id),
+                None => self.tick(),
+            }
+        }
+    }
+
+    /// The actor that can act with the most energy. The player goes first on ties.
+    fn next_turn(&self) -> Option<Turn>
+    {
+        let mut next = None;
+        let mut most = ACTION_COST as i32 - 1;
+
+        if self.player.actor.can_act() {
+            next = Some(Turn::Player);
+            most = self.player.actor.energy;
+        }
+
+        for (id, e) in self.dungeon.entities().iter() {
+            if let Some(actor) = e.actor {
+                if actor.energy > most {
+                    next = Some(Turn::Entity(id));
+                    most = actor.energy;
+                }
+            }
+        }
+
+        next
+    }
+
+    /// Gives every actor on the current level its energy for one tick.
+    fn tick(&mut self)
+    {
+        self.turn += 1;
+        self.log.set_turn(self.turn);
+        self.player.actor.tick();
+
+        for id in self.actor_ids() {
+            let actor = self.dungeon.entities_mut().get_mut(id).and_then(|e| e.actor.as_mut());
+            if let Some(actor) = actor {
+                actor.tick();
+            }
+        }
+    }
+
+    fn actor_ids(&self) -> Vec<EntityId>
+    {
+        self.dungeon.entities()
+            .iter()
+            .filter(|(_, e)| e.actor.is_some())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn entity_act(&mut self, id: EntityId)
+    {
+        let sees_player = match self.dungeon.entities().get(id) {
+            Some(e) => self.is_visible(e.pos),
+            None => return,
+        };
+
+        let level = self.dungeon.level();
+        let decision = ai::think(level, id, self.player.pos, sees_player, &mut self.rng);
+
+        let cost = match decision {
+            Some((state, action)) => {
+                let ai = self.dungeon.entities_mut().get_mut(id).and_then(|e| e.ai.as_mut());
+                if let Some(ai) = ai {
+                    ai.state = state;
+                }
+
+                match action {
+                    Action::Wait => ACTION_COST,
+                    Action::Move(pos) => {
+                        if let Some(e) = self.dungeon.entities_mut().get_mut(id) {
+                            e.pos = pos;
+                        }
+                        self.dungeon.map().cell(pos).props().move_cost
+                    },
+                    Action::Attack => {
+                        self.entity_attack(id);
+                        ACTION_COST
+                    },
+                }
+            },
+            None => ACTION_COST,
+        };
+
+        let actor = self.dungeon.entities_mut().get_mut(id).and_then(|e| e.actor.as_mut());
+        if let Some(actor) = actor {
+            actor.spend(cost);
+        }
+    }
+
+    /// A monster attacks the player.
+    fn entity_attack(&mut self, id: EntityId)
+    {
+        let (name, stats) = match self.dungeon.entities().get(id) {
+            Some(Entity { name, stats: Some(stats), .. }) => (name.clone(), *stats),
+            _ => return,
+        };
+
+        let mut defender = self.player.combat_stats();
+        let result = combat::melee(&stats, &mut defender, &mut self.rng);
+        self.player.stats.hp = defender.hp;
+
+        match result {
+            Attack::Miss => self.log.log(format!("The {} misses you.", name)),
+            Attack::Hit { damage, killed } => {
+                let msg = format!("The {} hits you for {}.", name, damage);
+                self.log.log_as(Severity::Danger, msg);
+                if killed {
+                    self.killed_by = Some(name);
+                }
+            },
+        }
+    }
+
+    /// A hostile monster in the player's field of view.
+    fn visible_monster(&self) -> Option<&Entity>
+    {
+        self.dungeon.entities()
+            .iter()
+            .map(|(_, e)| e)
+            .find(|e| e.kind == Kind::Monster && self.is_visible(e.pos))
+    }
+
+    /// Player action: pick up the items lying where the player stands.
+    pub fn player_pickup(&mut self) -> PickupOutcome
+    {
+        let outcome = self.pick_up_here();
+        if let PickupOutcome::PickedUp { items, .. } = &outcome {
+            if !items.is_empty() {
+                self.player.actor.spend(ACTION_COST);
+            }
+        }
+
+        outcome
+    }
+
+    /// Moves the items lying where the player stands to the inventory, without taking any time.
+    fn pick_up_here(&mut self) -> PickupOutcome
+    {
+        let here: Vec<EntityId> = self.dungeon.entities()
+            .items_at(self.player.pos)
+            .map(|(id, _)| id)
+            .collect();
+        if here.is_empty() {
+            return PickupOutcome::NothingHere;
+        }
+
+        let mut items = Vec::new();
+        let mut left = None;
+
+        for id in here {
+            let item = match self.dungeon.entities().get(id).and_then(|e| e.item.as_ref()) {
+                Some(item) => item,
+                None => continue,
+            };
+
+            if let Err(e) = self.player.inventory.can_carry(item) {
+                left = Some((item.name.clone(), e));
+                break;
+            }
+
+            if let Some(item) = self.dungeon.entities_mut().remove(id).and_then(|e| e.item) {
+                items.push(item.name.clone());
+                // Can't fail, it's been checked above.
+                let _ = self.player.inventory.add(item);
+            }
+        }
+
+        PickupOutcome::PickedUp { items, left }
+    }
+
+    /// Player action: use, equip or unequip an item of the inventory, depending on what the item
+    /// is and where it is.
+    pub fn player_use_item(&mut self, entry: Entry) -> ItemOutcome
+    {
+        let inventory = &mut self.player.inventory;
+
+        let outcome = match entry {
+            Entry::Equipped(slot) => {
+                let name = match inventory.equipped(slot) {
+                    Some(item) => item.name.clone(),
+                    None => return ItemOutcome::Missing,
+                };
+
+                match inventory.unequip(slot) {
+                    Ok(()) => ItemOutcome::Unequipped(name),
+                    Err(_) => return ItemOutcome::PackFull,
+                }
+            },
+            Entry::Pack(index) => {
+                let item = match inventory.pack.get(index) {
+                    Some(item) => item,
+                    None => return ItemOutcome::Missing,
+                };
+
+                match item.kind {
+                    ItemKind::Weapon { .. } | ItemKind::Armour { .. } => {
+                        let name = item.name.clone();
+                        inventory.equip(index);
+                        ItemOutcome::Equipped(name)
+                    },
+                    ItemKind::Consumable(Effect::Heal(amount)) => {
+                        let name = item.name.clone();
+                        inventory.remove(index);
+
+                        let stats = &mut self.player.stats;
+                        let healed = amount.min(stats.max_hp - stats.hp).max(0);
+                        stats.hp += healed;
+
+                        ItemOutcome::Healed { item: name, hp: healed }
+                    },
+                    ItemKind::Misc => return ItemOutcome::CantUse(item.name.clone()),
+                }
+            },
+        };
+
+        self.player.actor.spend(ACTION_COST);
+
+        outcome
+    }
+
+    /// Player action: drop an item of the inventory.
+    pub fn player_drop(&mut self, entry: Entry) -> ItemOutcome
+    {
+        let item = match self.player.inventory.take(entry) {
+            Some(item) => item,
+            None => return ItemOutcome::Missing,
+        };
+
+        let name = item.name.clone();
+        self.dungeon.entities_mut().spawn(Entity::from_item(item, self.player.pos));
+        self.player.actor.spend(ACTION_COST);
+
+        ItemOutcome::Dropped(name)
+    }
+
+    /// Player action: do nothing for a turn.
+    pub fn player_wait(&mut self)
+    {
+        self.player.actor.spend(ACTION_COST);
+    }
+
+    /// Player action: wait until something comes into sight.
+    pub fn player_rest(&mut self) -> RestOutcome
+    {
+        if let Some(e) = self.visible_monster() {
+            return RestOutcome::NotSafe(e.name.clone());
+        }
+
+        let start = self.turn;
+        while self.turn - start < MAX_REST_TURNS {
+            self.player_wait();
+            self.advance();
+            if self.player.stats.is_dead() {
+                return RestOutcome::Died;
+            }
+            self.update_fov();
+
+            if let Some(e) = self.visible_monster() {
+                return RestOutcome::Disturbed(e.name.clone());
+            }
+        }
+
+        RestOutcome::Rested(self.turn - start)
+    }
+
+    /// Player action: take the down staircase the player is standing on.
+    pub fn player_descend(&mut self) -> StairsOutcome
+    {
+        if self.dungeon.map().cell(self.player.pos) != tile::STAIRS_DOWN {
+            return StairsOutcome::NoStairs;
+        }
+
+        match self.dungeon.descend() {
+            Some(pos) => {
+                self.player.pos = pos;
+                self.player.actor.spend(ACTION_COST);
+                StairsOutcome::Changed
+            },
+            None => StairsOutcome::Bottom,
+        }
+    }
+
+    /// Player action: take the up staircase the player is standing on.
+    pub fn player_ascend(&mut self) -> StairsOutcome
+    {
+        if self.dungeon.map().cell(self.player.pos) != tile::STAIRS_UP {
+            return StairsOutcome::NoStairs;
+        }
+
+        match self.dungeon.ascend() {
+            Some(pos) => {
+                self.player.pos = pos;
+                self.player.actor.spend(ACTION_COST);
+                StairsOutcome::Changed
+            },
+            None => StairsOutcome::Top,
+        }
+    }
+
     /// Player action: move.
     /// Does not imply eny explicit movement type, this depends on the conditions of the player
-    /// character (e.g. can result in attacking, jumping, etc.).
-    pub fn player_move(&mut self, direction: Direction)
-    {
-        let (y_adjust, x_adjust): (i32, i32) = match direction {
-            Direction::Right => (0, 1),
-            Direction::Left => (0, -1),
-            Direction::Up => (-1, 0),
-            Direction::Down => (1, 0),
-            Direction::TopRight => (-1, 1),
-            Direction::TopLeft => (-1, -1),
-            Direction::DownRight => (1, 1),
-            Direction::DownLeft => (1, -1),
+    /// character (e.g. can result in attacking, jumping, etc.). What actually happens is decided
+    /// by whatever is at the target position.
+    pub fn player_move(&mut self, direction: Direction) -> MoveOutcome
+    {
+        let outcome = self.try_player_move(direction);
+
+        // Bumping into a wall doesn't take any time.
+        let cost = match outcome {
+            MoveOutcome::Blocked(_) => return outcome,
+            MoveOutcome::Moved | MoveOutcome::PickedUp(_) => {
+                let cost = self.dungeon.map().cell(self.player.pos).props().move_cost;
+                let burdened = self.player.conditions().contains(&Condition::Burdened);
+                if burdened { cost * 3 / 2 } else { cost }
+            },
+            _ => ACTION_COST,
         };
+        self.player.actor.spend(cost);
+
+        outcome
+    }
+
+    fn try_player_move(&mut self, direction: Direction) -> MoveOutcome
+    {
+        let (y_adjust, x_adjust) = direction.offset();
 
         let new_y = self.player.pos.y as i32 + y_adjust;
         let new_x = self.player.pos.x as i32 + x_adjust;
 
         if new_y < 0 || new_x < 0 {
-            return;
+            return MoveOutcome::Blocked(None);
+        }
+
+        let map = self.dungeon.map_mut();
+
+        if new_y >= map.height() as i32 || new_x >= map.width() as i32 {
+            return MoveOutcome::Blocked(None);
+        }
+
+        let new_pos = Pos { y: new_y as u32, x: new_x as u32 };
+        let tile = map.cell(new_pos).props();
+
+        if !tile.passable {
+            return match tile.bump {
+                Bump::Block => MoveOutcome::Blocked(Some(tile)),
+                Bump::Open(cell) => {
+                    map.set_cell(new_pos, cell);
+                    MoveOutcome::OpenedDoor
+                },
+                Bump::Interact(msg) => MoveOutcome::Interacted(msg.to_string()),
+            };
         }
 
-        if new_y >= self.map.height() as i32 || new_x >= self.map.width() as i32 {
-            return;
+        let entities = self.dungeon.entities_mut();
+        if let Some(id) = entities.blocking_at(new_pos) {
+            return self.player_bump(id);
         }
 
-        // TODO: more checks, resulting in more actions.
+        self.player.pos = new_pos;
+
+        match self.pick_up_here() {
+            PickupOutcome::NothingHere => MoveOutcome::Moved,
+            outcome => MoveOutcome::PickedUp(outcome),
+        }
+    }
+
+    /// The player bumps into a blocking entity. What happens depends on its components.
+    fn player_bump(&mut self, id: EntityId) -> MoveOutcome
+    {
+        let entities = self.dungeon.entities_mut();
+        let target = match entities.get_mut(id) {
+            Some(e) => e,
+            None => return MoveOutcome::Blocked(None),
+        };
+
+        if let Some(dialogue) = &target.dialogue {
+            return MoveOutcome::Interacted(dialogue.clone());
+        }
+
+        if let Some(stats) = &mut target.stats {
+            let result = combat::melee(&self.player.combat_stats(), stats, &mut self.rng);
+            let name = target.name.clone();
+            let mut level_up = None;
+
+            if let Attack::Hit { killed: true, .. } = result {
+                if self.player.gain_xp(combat::experience(stats)) {
+                    level_up = Some(self.player.level);
+                }
+                entities.remove(id);
+            }
+
+            return MoveOutcome::Attacked { target: name, result, level_up };
+        }
 
-        self.player.pos.y = new_y as u32;
-        self.player.pos.x = new_x as u32;
+        MoveOutcome::Blocked(None)
     }
 }
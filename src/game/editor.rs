@@ -0,0 +1,300 @@
+//! Map editor mode.
+
+use std::path::{Path, PathBuf};
+use termion::event::{Event, Key};
+use cwinui::style::{Color, TextStyle, WithStyle};
+
+use crate::gameui::{Form, Field};
+use crate::error::{self, Error};
+use crate::input::{self, Input};
+use crate::map::{Map, Cell};
+use crate::player::Pos;
+use crate::tile::{self, TILES};
+use crate::camera::Camera;
+use crate::direction::Direction;
+use crate::keymap::Command;
+use super::{Game, ACCENT_COLOR, MAP_HEIGHT, MAP_WIDTH};
+
+const EMPTY_CELL: Cell = tile::VOID;
+/// Largest height and width the map can be resized to.
+const MAX_SIZE: usize = 1024;
+
+struct Editor {
+    map: Map,
+    cursor: Pos,
+    brush: usize,
+    dirty: bool,
+}
+
+impl Editor {
+    fn move_cursor(&mut self, direction: Direction)
+    {
+        let (y_adjust, x_adjust) = direction.offset();
+        let new_y = self.cursor.y as i32 + y_adjust;
+        let new_x = self.cursor.x as i32 + x_adjust;
+
+        if new_y < 0 || new_x < 0 {
+            return;
+        }
+
+        let new_pos = Pos { y: new_y as u32, x: new_x as u32 };
+        if self.map.in_bounds(new_pos) {
+            self.cursor = new_pos;
+        }
+    }
+
+    fn paint(&mut self)
+    {
+        if self.map.in_bounds(self.cursor) {
+            self.map.set_cell(self.cursor, TILES[self.brush].cell);
+            self.dirty = true;
+        }
+    }
+}
+
+impl Game {
+    pub(super) fn editor(&mut self) -> error::Result<()>
+    {
+        let path = self.map_path.clone();
+        let map = if path.exists() {
+            match editor_load(&path) {
+                Ok(map) => map,
+                Err(source) => {
+                    return self.error_dialog(&Error::Map { path, source });
+                },
+            }
+        } else {
+            let mut map = Map::new(0, 0);
+            map.resize(MAP_HEIGHT, MAP_WIDTH, EMPTY_CELL);
+            map
+        };
+
+        let mut editor = Editor {
+            cursor: map.meta.spawn,
+            map,
+            brush: 0,
+            dirty: false,
+        };
+        let mut quit_pending = false;
+
+        self.editor_draw(&editor);
+
+        loop {
+            let mut status = None;
+
+            let event = match input::next()? {
+                Input::Event(event) => event,
+                Input::Resize => {
+                    self.relayout();
+                    self.editor_draw(&editor);
+                    continue;
+                },
+            };
+
+            let command = match event {
+                Event::Key(key) => self.keymap.command(key),
+                _ => None,
+            };
+
+            // The cursor moves with the same keys as the player, they take precedence over the
+            // keys of the editor.
+            if let Some(Command::Move(direction)) = command {
+                editor.move_cursor(direction);
+            } else {
+                match event {
+                    Event::Key(Key::Char(' '))
+                    | Event::Key(Key::Char('\n')) => editor.paint(),
+                    Event::Key(Key::Char('\t')) => editor.brush = (editor.brush + 1) % TILES.len(),
+                    Event::Key(Key::BackTab) => {
+                        editor.brush = (editor.brush + TILES.len() - 1) % TILES.len()
+                    },
+                    Event::Key(Key::Char(c @ '1'..='9')) => {
+                        let i = c as usize - '1' as usize;
+                        if i < TILES.len() {
+                            editor.brush = i;
+                        }
+                    },
+                    Event::Key(Key::Char('s')) => {
+                        editor.map.meta.spawn = editor.cursor;
+                        editor.dirty = true;
+                    },
+                    Event::Key(Key::Char('r')) => {
+                        if let Some((height, width)) = self.editor_ask_size(&editor.map)? {
+                            editor.map.resize(height, width, EMPTY_CELL);
+                            editor.cursor.y = editor.cursor.y.min(height.saturating_sub(1) as u32);
+                            editor.cursor.x = editor.cursor.x.min(width.saturating_sub(1) as u32);
+                            editor.dirty = true;
+                        }
+                    },
+                    Event::Key(Key::Char('w')) => {
+                        status = Some(match editor_save(&editor.map, &path) {
+                            Ok(()) => {
+                                editor.dirty = false;
+                                format!("Saved {}.", path.display())
+                            },
+                            Err(e) => format!("Couldn't save {}: {}", path.display(), e),
+                        });
+                    },
+                    Event::Key(Key::Char('e')) => {
+                        let text_path = text_path(&path);
+                        status = Some(match std::fs::write(&text_path, editor.map.to_text()) {
+                            Ok(()) => format!("Exported {}.", text_path.display()),
+                            Err(e) => format!("Couldn't export {}: {}", text_path.display(), e),
+                        });
+                    },
+                    Event::Key(Key::Char('i')) => {
+                        let text_path = text_path(&path);
+                        status = Some(match editor_import(&text_path) {
+                            Ok(map) => {
+                                editor.cursor = map.meta.spawn;
+                                editor.map = map;
+                                editor.dirty = true;
+                                format!("Imported {}.", text_path.display())
+                            },
+                            Err(e) => format!("Couldn't import {}: {}", text_path.display(), e),
+                        });
+                    },
+                    Event::Key(Key::Char('q')) => {
+                        if !editor.dirty || quit_pending {
+                            break;
+                        }
+                        quit_pending = true;
+                        status = Some("Unsaved changes. Press q again to quit.".to_string());
+                    },
+                    _ => {},
+                }
+            }
+
+            if status.is_none() {
+                quit_pending = false;
+            }
+
+            self.editor_draw(&editor);
+            if let Some(msg) = status {
+                self.set_status(&msg);
+            }
+        }
+
+        self.clear_main_frame();
+        self.set_status("");
+
+        Ok(())
+    }
+
+    fn editor_draw(&mut self, editor: &Editor)
+    {
+        let mut camera = Camera::new(
+            self.ui.main_frame.content_height(),
+            self.ui.main_frame.content_width(),
+        );
+        camera.follow(editor.cursor, editor.map.height(), editor.map.width());
+
+        for y in 0..camera.height() {
+            for x in 0..camera.width() {
+                let pos = camera.to_map(y, x);
+
+                if editor.map.in_bounds(pos) {
+                    let cell = editor.map.cell(pos);
+                    let color = cell.props().color;
+                    self.ui.main_frame.putc(
+                        y as u32,
+                        x as u32,
+                        cell.glyph().with_style(|s| s.fg_color(Color::Rgb(color))),
+                    );
+                } else {
+                    self.ui.main_frame.putc(y as u32, x as u32, ' ');
+                }
+            }
+        }
+
+        if let Some((y, x)) = camera.to_view(editor.map.meta.spawn) {
+            self.ui.main_frame.putc(y, x, '@');
+        }
+
+        let cursor = editor.cursor;
+        if let Some((y, x)) = camera.to_view(cursor).filter(|_| editor.map.in_bounds(cursor)) {
+            let c = match editor.map.cell(cursor).glyph() {
+                ' ' => '_',
+                c => c,
+            };
+            self.ui.main_frame.putc(
+                y,
+                x,
+                c.with_style(|s|
+                    s.fg_color(Color::Rgb(ACCENT_COLOR))
+                     .text_style(TextStyle::BOLD | TextStyle::UNDERLINE)
+                ),
+            );
+        }
+
+        let palette: String = TILES.iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let mark = if i == editor.brush { '*' } else { ' ' };
+                format!("{}{}:{}", mark, i + 1, t.glyph)
+            })
+            .collect();
+        self.set_status(&format!(
+            "({},{}) {}{}  s:spawn r:resize w:save e:export i:import q:quit",
+            cursor.y,
+            cursor.x,
+            palette,
+            if editor.dirty { " [+]" } else { "" },
+        ));
+    }
+
+    /// Asks for new map dimensions. Returns `None` if the user cancelled.
+    fn editor_ask_size(&mut self, map: &Map) -> error::Result<Option<(usize, usize)>>
+    {
+        let max = MAX_SIZE as i64;
+        let fields = vec![
+            Field::number("Height", 1, max, map.height() as i64),
+            Field::number("Width", 1, max, map.width() as i64),
+        ];
+        let form = Form::new(0, 0, 5, 33, fields).expect("the size form fits its fields");
+
+        self.set_status(&format!(
+            "Current size: {}x{}. Enter to confirm, esc to cancel.",
+            map.height(),
+            map.width()
+        ));
+
+        let values = match self.ask(form)? {
+            Some(values) => values,
+            None => return Ok(None),
+        };
+
+        Ok(Some((values.number("Height") as usize, values.number("Width") as usize)))
+    }
+}
+
+fn editor_load(path: &Path) -> Result<Map, crate::serial::Error>
+{
+    let mut f = std::fs::File::open(path)?;
+    Map::from_reader(&mut f)
+}
+
+/// Reads a map exported with `e`, see `Map::to_text`.
+fn editor_import(path: &Path) -> Result<Map, Box<dyn std::error::Error>>
+{
+    let text = std::fs::read_to_string(path)?;
+    Ok(Map::from_text(&text)?)
+}
+
+fn editor_save(map: &Map, path: &Path) -> Result<(), crate::serial::Error>
+{
+    let mut bytes = Vec::new();
+    map.save(&mut bytes)?;
+
+    crate::serial::write_file(path, &bytes)
+}
+
+/// Where the map is exported to and imported from as text: the map file with `.txt` appended,
+/// so that it's never the map file itself.
+fn text_path(path: &Path) -> PathBuf
+{
+    let mut name = path.as_os_str().to_owned();
+    name.push(".txt");
+
+    PathBuf::from(name)
+}
@@ -0,0 +1,651 @@
+//! Save files.
+//!
+//! A save is a `serial::Container` with the `RGSV` magic. Each part of the `GameState` lives in
+//! its own section, so new state can be added as new sections. Bump `VERSION` whenever an
+//! existing section changes its layout.
+
+use std::path::PathBuf;
+
+use crate::serial::{self, Container, Encoder, Decoder};
+use crate::player::{Player, Pos};
+use crate::map::Map;
+use crate::dungeon::{self, Dungeon, Level};
+use crate::entity::{Entity, EntityStore, Kind, Ai, AiState};
+use crate::combat::Stats;
+use crate::character::{Race, Class, Attributes};
+use crate::item::{Item, ItemKind, Effect, Inventory, MAX_ITEMS};
+use crate::rng::Rng;
+use crate::turn::Actor;
+use super::GameState;
+
+const MAGIC: [u8; 4] = *b"RGSV";
+const VERSION: u32 = 11;
+
+const SAVE_DIR: &str = "saves";
+pub const SLOT_COUNT: usize = 3;
+
+const PLAYER_SECTION: [u8; 4] = *b"PLYR";
+const DUNGEON_SECTION: [u8; 4] = *b"DUNG";
+const WORLD_SECTION: [u8; 4] = *b"WRLD";
+
+/// Each visited level has its own section, `LV00`, `LV01`, etc.
+fn level_section(depth: usize) -> [u8; 4]
+{
+    [b'L', b'V', b'0' + (depth / 10) as u8, b'0' + (depth % 10) as u8]
+}
+
+pub fn slot_path(slot: usize) -> PathBuf
+{
+    PathBuf::from(SAVE_DIR).join(format!("slot{}.sav", slot + 1))
+}
+
+/// Name of the character saved in the slot, or `None` if the slot is empty or unreadable.
+pub fn slot_summary(slot: usize) -> Option<String>
+{
+    let mut f = std::fs::File::open(slot_path(slot)).ok()?;
+    let container = Container::read(MAGIC, &mut f).ok()?;
+    let mut dec = container.section(PLAYER_SECTION)?;
+
+    dec.get_str().ok()
+}
+
+pub fn save(state: &GameState, slot: usize) -> Result<(), serial::Error>
+{
+    std::fs::create_dir_all(SAVE_DIR)?;
+    let mut bytes = Vec::new();
+    encode(state)?.write(MAGIC, &mut bytes)?;
+
+    serial::write_file(&slot_path(slot), &bytes)
+}
+
+pub fn load(slot: usize) -> Result<GameState, serial::Error>
+{
+    let mut f = std::fs::File::open(slot_path(slot))?;
+
+    decode(&Container::read(MAGIC, &mut f)?)
+}
+
+fn encode(state: &GameState) -> Result<Container, serial::Error>
+{
+    let mut container = Container::new(VERSION);
+    container.add_section(PLAYER_SECTION, encode_player(&state.player));
+
+    let dungeon = &state.dungeon;
+    let mut enc = Encoder::new();
+    enc.put_u64(dungeon.seed());
+    enc.put_u32(dungeon.depth() as u32);
+    enc.put_u32(dungeon.levels().len() as u32);
+    container.add_section(DUNGEON_SECTION, enc);
+
+    let mut enc = Encoder::new();
+    enc.put_u64(state.turn);
+    enc.put_u64(state.rng.state());
+    container.add_section(WORLD_SECTION, enc);
+
+    for (depth, level) in dungeon.levels().iter().enumerate() {
+        container.add_section(level_section(depth), encode_level(level)?);
+    }
+
+    Ok(container)
+}
+
+fn decode(container: &Container) -> Result<GameState, serial::Error>
+{
+    if container.version != VERSION {
+        return Err(serial::Error::UnsupportedVersion(container.version));
+    }
+
+    let mut dec = container.require_section(DUNGEON_SECTION)?;
+    let seed = dec.get_u64()?;
+    let current = dec.get_u32()? as usize;
+    let count = dec.get_u32()? as usize;
+    if count > dungeon::DEPTH {
+        return Err(serial::Error::Corrupt("too many dungeon levels"));
+    }
+
+    let mut levels = Vec::new();
+    for depth in 0..count {
+        levels.push(decode_level(&mut container.require_section(level_section(depth))?)?);
+    }
+    let dungeon = Dungeon::from_parts(seed, levels, current)
+        .ok_or(serial::Error::Corrupt("invalid dungeon levels"))?;
+
+    let mut state = GameState::new(
+        decode_player(&mut container.require_section(PLAYER_SECTION)?)?,
+        dungeon,
+    );
+
+    let mut dec = container.require_section(WORLD_SECTION)?;
+    state.turn = dec.get_u64()?;
+    state.log.set_turn(state.turn);
+    state.rng = Rng::new(dec.get_u64()?);
+
+    if !state.dungeon.map().in_bounds(state.player.pos) {
+        return Err(serial::Error::Corrupt("player is outside of the map"));
+    }
+
+    Ok(state)
+}
+
+fn encode_player(player: &Player) -> Encoder
+{
+    let mut enc = Encoder::new();
+    enc.put_str(&player.name);
+    enc.put_u32(player.pos.y);
+    enc.put_u32(player.pos.x);
+    encode_actor(&mut enc, &player.actor);
+    encode_stats(&mut enc, &player.stats);
+    encode_inventory(&mut enc, &player.inventory);
+    enc.put_u32(player.level);
+    enc.put_u32(player.xp);
+    encode_character(&mut enc, player);
+
+    enc
+}
+
+fn decode_player(dec: &mut Decoder) -> Result<Player, serial::Error>
+{
+    let mut player = Player::new();
+    player.name = dec.get_str()?;
+    player.pos = Pos {
+        y: dec.get_u32()?,
+        x: dec.get_u32()?,
+    };
+    player.actor = decode_actor(dec)?;
+    player.stats = decode_stats(dec)?;
+    player.inventory = decode_inventory(dec)?;
+    player.level = dec.get_u32()?;
+    if player.level == 0 {
+        return Err(serial::Error::Corrupt("player level is 0"));
+    }
+    player.xp = dec.get_u32()?;
+    decode_character(dec, &mut player)?;
+
+    Ok(player)
+}
+
+/// A level is stored as a whole map file, in the format written by `Map::save`, followed by
+/// the explored layer and the entities.
+fn encode_level(level: &Level) -> Result<Encoder, serial::Error>
+{
+    let mut bytes = Vec::new();
+    level.map.save(&mut bytes)?;
+
+    let mut enc = Encoder::new();
+    enc.put_u32(bytes.len() as u32);
+    enc.put_bytes(&bytes);
+    encode_explored(&mut enc, &level.map);
+    encode_entities(&mut enc, &level.entities);
+
+    Ok(enc)
+}
+
+fn decode_level(dec: &mut Decoder) -> Result<Level, serial::Error>
+{
+    let len = dec.get_u32()? as usize;
+    let mut map = Map::from_reader(&mut dec.get_bytes(len)?)?;
+    decode_explored(dec, &mut map)?;
+    let entities = decode_entities(dec, &map)?;
+
+    Ok(Level { map, entities })
+}
+
+/// One bit per cell, row-major, least significant bit first.
+fn encode_explored(enc: &mut Encoder, map: &Map)
+{
+    for chunk in map.explored().chunks(8) {
+        let mut byte = 0;
+        for (i, explored) in chunk.iter().enumerate() {
+            byte |= (*explored as u8) << i;
+        }
+        enc.put_u8(byte);
+    }
+}
+
+fn decode_explored(dec: &mut Decoder, map: &mut Map) -> Result<(), serial::Error>
+{
+    let len = map.height() * map.width();
+    let bytes = dec.get_bytes(len.div_ceil(8))?;
+    let explored = (0..len)
+        .map(|i| bytes[i / 8] & (1 << (i % 8)) != 0)
+        .collect();
+
+    if !map.set_explored_layer(explored) {
+        return Err(serial::Error::Corrupt("explored layer doesn't match the map"));
+    }
+
+    Ok(())
+}
+
+/// Every slot of the store is saved, empty ones included, so entity ids stay the same.
+fn encode_entities(enc: &mut Encoder, entities: &EntityStore)
+{
+    enc.put_u32(entities.slots().len() as u32);
+
+    for slot in entities.slots() {
+        enc.put_bool(slot.is_some());
+        if let Some(e) = slot {
+            encode_entity(enc, e);
+        }
+    }
+}
+
+fn decode_entities(dec: &mut Decoder, map: &Map) -> Result<EntityStore, serial::Error>
+{
+    let count = dec.get_u32()? as usize;
+
+    let mut slots = Vec::new();
+    for _ in 0..count {
+        if !dec.get_bool()? {
+            slots.push(None);
+            continue;
+        }
+
+        let e = decode_entity(dec)?;
+        if !map.in_bounds(e.pos) {
+            return Err(serial::Error::Corrupt("entity is outside of the map"));
+        }
+        slots.push(Some(e));
+    }
+
+    Ok(EntityStore::from_slots(slots))
+}
+
+fn encode_entity(enc: &mut Encoder, e: &Entity)
+{
+    enc.put_u8(match e.kind {
+        Kind::Monster => 0,
+        Kind::Item => 1,
+        Kind::Npc => 2,
+    });
+    enc.put_str(&e.name);
+    enc.put_u32(e.pos.y);
+    enc.put_u32(e.pos.x);
+    enc.put_u32(e.glyph as u32);
+    enc.put_u8(e.color.0);
+    enc.put_u8(e.color.1);
+    enc.put_u8(e.color.2);
+
+    enc.put_bool(e.stats.is_some());
+    if let Some(stats) = &e.stats {
+        encode_stats(enc, stats);
+    }
+
+    enc.put_bool(e.ai.is_some());
+    if let Some(ai) = &e.ai {
+        encode_ai(enc, ai);
+    }
+
+    enc.put_bool(e.actor.is_some());
+    if let Some(actor) = &e.actor {
+        encode_actor(enc, actor);
+    }
+
+    enc.put_bool(e.dialogue.is_some());
+    if let Some(dialogue) = &e.dialogue {
+        enc.put_str(dialogue);
+    }
+
+    encode_item_option(enc, &e.item);
+}
+
+fn decode_entity(dec: &mut Decoder) -> Result<Entity, serial::Error>
+{
+    let kind = match dec.get_u8()? {
+        0 => Kind::Monster,
+        1 => Kind::Item,
+        2 => Kind::Npc,
+        _ => return Err(serial::Error::Corrupt("unknown entity kind")),
+    };
+    let name = dec.get_str()?;
+    let pos = Pos {
+        y: dec.get_u32()?,
+        x: dec.get_u32()?,
+    };
+    let glyph = char::from_u32(dec.get_u32()?)
+        .ok_or(serial::Error::Corrupt("invalid entity glyph"))?;
+    let color = (dec.get_u8()?, dec.get_u8()?, dec.get_u8()?);
+
+    let stats = if dec.get_bool()? { Some(decode_stats(dec)?) } else { None };
+
+    let ai = if dec.get_bool()? { Some(decode_ai(dec)?) } else { None };
+
+    let actor = if dec.get_bool()? { Some(decode_actor(dec)?) } else { None };
+    let dialogue = if dec.get_bool()? { Some(dec.get_str()?) } else { None };
+    let item = decode_item_option(dec)?;
+
+    Ok(Entity { kind, name, pos, glyph, color, stats, ai, actor, dialogue, item })
+}
+
+fn encode_actor(enc: &mut Encoder, actor: &Actor)
+{
+    enc.put_u32(actor.speed);
+    enc.put_i32(actor.energy);
+}
+
+fn decode_actor(dec: &mut Decoder) -> Result<Actor, serial::Error>
+{
+    let speed = dec.get_u32()?;
+    if speed == 0 {
+        return Err(serial::Error::Corrupt("actor has no speed"));
+    }
+
+    Ok(Actor {
+        speed,
+        energy: dec.get_i32()?,
+    })
+}
+
+fn encode_stats(enc: &mut Encoder, stats: &Stats)
+{
+    enc.put_i32(stats.hp);
+    enc.put_i32(stats.max_hp);
+    enc.put_i32(stats.attack);
+    enc.put_i32(stats.defense);
+    enc.put_i32(stats.accuracy);
+}
+
+fn decode_stats(dec: &mut Decoder) -> Result<Stats, serial::Error>
+{
+    Ok(Stats {
+        hp: dec.get_i32()?,
+        max_hp: dec.get_i32()?,
+        attack: dec.get_i32()?,
+        defense: dec.get_i32()?,
+        accuracy: dec.get_i32()?,
+    })
+}
+
+fn encode_ai(enc: &mut Encoder, ai: &Ai)
+{
+    match ai.state {
+        AiState::Idle => enc.put_u8(0),
+        AiState::Chase { last_seen } => {
+            enc.put_u8(1);
+            enc.put_u32(last_seen.y);
+            enc.put_u32(last_seen.x);
+        },
+        AiState::Flee => enc.put_u8(2),
+    }
+
+    enc.put_bool(ai.post.is_some());
+    if let Some(post) = ai.post {
+        enc.put_u32(post.y);
+        enc.put_u32(post.x);
+    }
+
+    enc.put_i32(ai.flee_percent);
+}
+
+fn decode_ai(dec: &mut Decoder) -> Result<Ai, serial::Error>
+{
+    let state = match dec.get_u8()? {
+        0 => AiState::Idle,
+        1 => AiState::Chase {
+            last_seen: Pos {
+                y: dec.get_u32()?,
+                x: dec.get_u32()?,
+            },
+        },
+        2 => AiState::Flee,
+        _ => return Err(serial::Error::Corrupt("unknown AI state")),
+    };
+
+    let post = if dec.get_bool()? {
+        Some(Pos {
+            y: dec.get_u32()?,
+            x: dec.get_u32()?,
+        })
+    } else {
+        None
+    };
+
+    Ok(Ai {
+        state,
+        post,
+        flee_percent: dec.get_i32()?,
+    })
+}
+
+fn encode_inventory(enc: &mut Encoder, inventory: &Inventory)
+{
+    enc.put_u32(inventory.pack.len() as u32);
+    for item in &inventory.pack {
+        encode_item(enc, item);
+    }
+    encode_item_option(enc, &inventory.weapon);
+    encode_item_option(enc, &inventory.armour);
+}
+
+fn decode_inventory(dec: &mut Decoder) -> Result<Inventory, serial::Error>
+{
+    let count = dec.get_u32()? as usize;
+    if count > MAX_ITEMS {
+        return Err(serial::Error::Corrupt("too many items in the inventory"));
+    }
+
+    let mut inventory = Inventory::new();
+    for _ in 0..count {
+        inventory.pack.push(decode_item(dec)?);
+    }
+    inventory.weapon = decode_item_option(dec)?;
+    inventory.armour = decode_item_option(dec)?;
+
+    Ok(inventory)
+}
+
+fn encode_item_option(enc: &mut Encoder, item: &Option<Item>)
+{
+    enc.put_bool(item.is_some());
+    if let Some(item) = item {
+        encode_item(enc, item);
+    }
+}
+
+fn decode_item_option(dec: &mut Decoder) -> Result<Option<Item>, serial::Error>
+{
+    if dec.get_bool()? { Ok(Some(decode_item(dec)?)) } else { Ok(None) }
+}
+
+fn encode_item(enc: &mut Encoder, item: &Item)
+{
+    enc.put_str(&item.name);
+    enc.put_u32(item.glyph as u32);
+    enc.put_u8(item.color.0);
+    enc.put_u8(item.color.1);
+    enc.put_u8(item.color.2);
+    enc.put_u32(item.weight);
+
+    match item.kind {
+        ItemKind::Weapon { attack, accuracy } => {
+            enc.put_u8(0);
+            enc.put_i32(attack);
+            enc.put_i32(accuracy);
+        },
+        ItemKind::Armour { defense } => {
+            enc.put_u8(1);
+            enc.put_i32(defense);
+        },
+        ItemKind::Consumable(Effect::Heal(hp)) => {
+            enc.put_u8(2);
+            enc.put_i32(hp);
+        },
+        ItemKind::Misc => enc.put_u8(3),
+    }
+}
+
+fn decode_item(dec: &mut Decoder) -> Result<Item, serial::Error>
+{
+    let name = dec.get_str()?;
+    let glyph = char::from_u32(dec.get_u32()?)
+        .ok_or(serial::Error::Corrupt("invalid item glyph"))?;
+    let color = (dec.get_u8()?, dec.get_u8()?, dec.get_u8()?);
+    let weight = dec.get_u32()?;
+
+    let kind = match dec.get_u8()? {
+        0 => ItemKind::Weapon {
+            attack: dec.get_i32()?,
+            accuracy: dec.get_i32()?,
+        },
+        1 => ItemKind::Armour {
+            defense: dec.get_i32()?,
+        },
+        2 => ItemKind::Consumable(Effect::Heal(dec.get_i32()?)),
+        3 => ItemKind::Misc,
+        _ => return Err(serial::Error::Corrupt("unknown item kind")),
+    };
+
+    Ok(Item { name, glyph, color, weight, kind })
+}
+
+/// The choices made in character creation.
+fn encode_character(enc: &mut Encoder, player: &Player)
+{
+    let race = Race::ALL.iter().position(|r| *r == player.race).unwrap_or(0);
+    let class = Class::ALL.iter().position(|c| *c == player.class).unwrap_or(0);
+
+    enc.put_u8(race as u8);
+    enc.put_u8(class as u8);
+    enc.put_i32(player.attributes.strength);
+    enc.put_i32(player.attributes.dexterity);
+    enc.put_i32(player.attributes.constitution);
+}
+
+fn decode_character(dec: &mut Decoder, player: &mut Player) -> Result<(), serial::Error>
+{
+    player.race = *Race::ALL.get(dec.get_u8()? as usize)
+        .ok_or(serial::Error::Corrupt("unknown race"))?;
+    player.class = *Class::ALL.get(dec.get_u8()? as usize)
+        .ok_or(serial::Error::Corrupt("unknown class"))?;
+    player.attributes = Attributes {
+        strength: dec.get_i32()?,
+        dexterity: dec.get_i32()?,
+        constitution: dec.get_i32()?,
+    };
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityId;
+
+    fn sample() -> GameState
+    {
+        let attributes = Attributes {
+            strength: 14,
+            dexterity: 12,
+            constitution: 9,
+        };
+        let mut player = Player::create("Ayla".to_string(), Race::Elf, Class::Rogue, attributes);
+        player.level = 3;
+        player.xp = 17;
+        player.stats.hp -= 2;
+
+        let mut state = GameState::new(player, Dungeon::new(1234, None));
+        state.dungeon.descend();
+        state.dungeon.descend();
+        state.dungeon.ascend();
+        state.player.pos = state.dungeon.map().meta.spawn;
+        state.turn = 42;
+
+        let map = state.dungeon.map_mut();
+        for x in 0..10 {
+            map.set_explored(Pos { y: 1, x });
+        }
+
+        let entities = state.dungeon.entities_mut();
+        entities.remove(EntityId(0));
+        if let Some(ai) = entities.get_mut(EntityId(1)).and_then(|e| e.ai.as_mut()) {
+            ai.state = AiState::Chase { last_seen: Pos { y: 2, x: 3 } };
+            ai.post = Some(Pos { y: 4, x: 5 });
+        }
+
+        state
+    }
+
+    fn round_trip(container: &Container) -> Result<GameState, serial::Error>
+    {
+        let mut bytes = Vec::new();
+        container.write(MAGIC, &mut bytes)?;
+
+        decode(&Container::from_bytes(MAGIC, &bytes)?)
+    }
+
+    fn assert_same(a: &GameState, b: &GameState)
+    {
+        let (p, q) = (&a.player, &b.player);
+        assert_eq!(p.name, q.name);
+        assert_eq!((p.race, p.class, p.attributes), (q.race, q.class, q.attributes));
+        assert_eq!((p.pos, p.level, p.xp), (q.pos, q.level, q.xp));
+        assert_eq!(format!("{:?}", p.actor), format!("{:?}", q.actor));
+        assert_eq!(format!("{:?}", p.stats), format!("{:?}", q.stats));
+        assert_eq!(format!("{:?}", p.inventory), format!("{:?}", q.inventory));
+
+        assert_eq!(a.turn, b.turn);
+        assert_eq!(a.rng.state(), b.rng.state());
+        assert_eq!(a.dungeon.seed(), b.dungeon.seed());
+        assert_eq!(a.dungeon.depth(), b.dungeon.depth());
+        assert_eq!(a.dungeon.levels().len(), b.dungeon.levels().len());
+
+        for (x, y) in a.dungeon.levels().iter().zip(b.dungeon.levels()) {
+            assert_eq!((x.map.height(), x.map.width()), (y.map.height(), y.map.width()));
+            assert_eq!(x.map.grid, y.map.grid);
+            assert_eq!(x.map.explored(), y.map.explored());
+            assert_eq!(
+                format!("{:?}", x.entities.slots()),
+                format!("{:?}", y.entities.slots()),
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_the_state()
+    {
+        let state = sample();
+        assert_eq!(state.dungeon.levels().len(), 3);
+        assert!(state.dungeon.entities().slots()[0].is_none());
+        assert!(!state.player.inventory.pack.is_empty());
+
+        assert_same(&state, &round_trip(&encode(&state).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn bad_version()
+    {
+        let mut container = encode(&sample()).unwrap();
+        container.version = VERSION + 1;
+
+        assert!(matches!(
+            round_trip(&container),
+            Err(serial::Error::UnsupportedVersion(v)) if v == VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn truncated_file()
+    {
+        let mut bytes = Vec::new();
+        encode(&sample()).unwrap().write(MAGIC, &mut bytes).unwrap();
+        bytes.truncate(bytes.len() / 2);
+
+        assert!(matches!(Container::from_bytes(MAGIC, &bytes), Err(serial::Error::Truncated)));
+    }
+
+    #[test]
+    fn too_many_levels()
+    {
+        let mut enc = Encoder::new();
+        enc.put_u64(1234);
+        enc.put_u32(0);
+        enc.put_u32(dungeon::DEPTH as u32 + 1);
+        let mut container = Container::new(VERSION);
+        container.add_section(DUNGEON_SECTION, enc);
+
+        assert!(matches!(
+            decode(&container),
+            Err(serial::Error::Corrupt("too many dungeon levels"))
+        ));
+    }
+}
@@ -0,0 +1,748 @@
+use cwinui::style::{Color, OwnedStyledText, StyledText, TextStyle, WithStyle};
+use termion::event::{Event, Key};
+
+use cwinui::widget::{
+    Widget,
+    InteractiveWidget,
+    OutputWidget,
+    InnerWidget,
+    Window,
+    PoisonError,
+};
+use cwinui::sub_impl_aligned;
+use cwinui::sub_impl_alignable;
+
+const ERROR_COLOR: (u8, u8, u8) = (0xff, 0x50, 0x50);
+
+/// Width of the ": " between the labels and the values.
+const SPACER_WIDTH: usize = 2;
+/// Empty columns on the left and right of the form.
+const MARGIN: usize = 3;
+
+/// Check of a `Validator::Custom`, returning the error message if the value isn't valid.
+pub type CustomCheck = Box<dyn Fn(&str) -> Result<(), String>>;
+
+/// A check on the text of a text or number field.
+pub enum Validator {
+    /// Not empty, or only whitespace.
+    Required,
+    /// At most this many characters.
+    MaxLength(usize),
+    /// Only characters for which the function returns `true`.
+    AllowedChars(fn(char) -> bool),
+    /// The form is made wide enough for messages of `message_width` characters, longer ones are
+    /// cut off.
+    Custom {
+        check: CustomCheck,
+        message_width: usize,
+    },
+}
+
+impl Validator {
+    pub fn custom<F>(message_width: usize, check: F) -> Self
+    where
+        F: Fn(&str) -> Result<(), String> + 'static,
+    {
+        Validator::Custom {
+            check: Box::new(check),
+            message_width,
+        }
+    }
+
+    fn check(&self, value: &str) -> Result<(), String>
+    {
+        match self {
+            Validator::Required => {
+                if value.trim().is_empty() {
+                    return Err(self.message(' '));
+                }
+            },
+            Validator::MaxLength(max) => {
+                if value.chars().count() > *max {
+                    return Err(self.message(' '));
+                }
+            },
+            Validator::AllowedChars(allowed) => {
+                if let Some(c) = value.chars().find(|c| !allowed(*c)) {
+                    return Err(self.message(c));
+                }
+            },
+            Validator::Custom { check, .. } => return check(value),
+        }
+
+        Ok(())
+    }
+
+    /// The error message, `c` being the offending character for `AllowedChars`.
+    fn message(&self, c: char) -> String
+    {
+        match self {
+            Validator::Required => "This field is required.".to_string(),
+            Validator::MaxLength(max) => format!("At most {} characters.", max),
+            Validator::AllowedChars(_) => format!("'{}' is not allowed.", c),
+            Validator::Custom { .. } => unreachable!("custom validators make their own messages"),
+        }
+    }
+
+    /// Columns the longest error message needs.
+    fn message_width(&self) -> usize
+    {
+        match self {
+            Validator::Custom { message_width, .. } => *message_width,
+            // Only the character of `AllowedChars` varies, and any character takes one column.
+            _ => self.message(' ').chars().count(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormError {
+    NoFields,
+    /// The form needs at least this size to fit its fields.
+    TooSmall {
+        height: usize,
+        width: usize,
+    },
+}
+
+impl std::fmt::Display for FormError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self {
+            FormError::NoFields => write!(f, "the form has no fields"),
+            FormError::TooSmall { height, width } => {
+                write!(f, "the form needs to be at least {}x{}", height, width)
+            },
+        }
+    }
+}
+
+impl std::error::Error for FormError {}
+
+enum Input {
+    Text(String),
+    Number {
+        text: String,
+        min: i64,
+        max: i64,
+    },
+    Checkbox(bool),
+    Choice {
+        options: Vec<String>,
+        selected: usize,
+    },
+}
+
+/// A labelled field of a `Form`.
+pub struct Field {
+    label: OwnedStyledText,
+    input: Input,
+    validators: Vec<Validator>,
+}
+
+impl Field {
+    pub fn text<'s, T: Into<StyledText<'s>>>(label: T, value: &str) -> Self
+    {
+        Self::new(label, Input::Text(value.to_string()))
+    }
+
+    /// A whole number between `min` and `max`, inclusive.
+    pub fn number<'s, T: Into<StyledText<'s>>>(label: T, min: i64, max: i64, value: i64) -> Self
+    {
+        Self::new(label, Input::Number { text: value.to_string(), min, max })
+    }
+
+    pub fn checkbox<'s, T: Into<StyledText<'s>>>(label: T, checked: bool) -> Self
+    {
+        Self::new(label, Input::Checkbox(checked))
+    }
+
+    /// One of `options`, picked with the left and right keys.
+    ///
+    /// Panics if there are no options.
+    pub fn choice<'s, T: Into<StyledText<'s>>>(label: T, options: &[&str], selected: usize) -> Self
+    {
+        assert!(!options.is_empty(), "a choice field needs options");
+
+        Self::new(label, Input::Choice {
+            options: options.iter().map(|o| o.to_string()).collect(),
+            selected: selected.min(options.len() - 1),
+        })
+    }
+
+    /// Adds a check on the text of the field. Checkboxes and choices are always valid, so their
+    /// validators are ignored.
+    pub fn with_validator(mut self, validator: Validator) -> Self
+    {
+        self.validators.push(validator);
+        self
+    }
+
+    fn new<'s, T: Into<StyledText<'s>>>(label: T, input: Input) -> Self
+    {
+        Self {
+            label: OwnedStyledText::from(label),
+            input,
+            validators: Vec::new(),
+        }
+    }
+
+    fn label_width(&self) -> usize
+    {
+        self.label.content.chars().count()
+    }
+
+    /// Columns the value needs.
+    fn min_width(&self) -> usize
+    {
+        match &self.input {
+            // Long text scrolls, but there needs to be room for the cursor.
+            Input::Text(_) => 2,
+            Input::Number { min, max, .. } => {
+                min.to_string().len().max(max.to_string().len()) + 1
+            },
+            Input::Checkbox(_) => 3,
+            Input::Choice { options, .. } => {
+                options.iter().map(|o| o.chars().count()).max().unwrap_or(0) + 4
+            },
+        }
+    }
+
+    /// Columns the longest error message of the field needs.
+    fn error_width(&self) -> usize
+    {
+        let range = match &self.input {
+            Input::Number { min, max, .. } => range_error(*min, *max).chars().count(),
+            _ => 0,
+        };
+
+        self.validators.iter()
+            .map(Validator::message_width)
+            .fold(range, usize::max)
+    }
+
+    fn validate(&self) -> Result<(), String>
+    {
+        let text = match &self.input {
+            Input::Text(text) => text,
+            Input::Number { text, min, max } => {
+                match text.parse::<i64>() {
+                    Ok(n) if (*min..=*max).contains(&n) => text,
+                    _ => return Err(range_error(*min, *max)),
+                }
+            },
+            Input::Checkbox(_) | Input::Choice { .. } => return Ok(()),
+        };
+
+        self.validators.iter().try_for_each(|v| v.check(text))
+    }
+
+    fn value(&self) -> Value
+    {
+        match &self.input {
+            Input::Text(text) => Value::Text(text.clone()),
+            Input::Number { text, .. } => Value::Number(text.parse().unwrap_or(0)),
+            Input::Checkbox(checked) => Value::Checkbox(*checked),
+            Input::Choice { selected, .. } => Value::Choice(*selected),
+        }
+    }
+
+    fn process_key(&mut self, key: Key)
+    {
+        match (&mut self.input, key) {
+            (Input::Text(text), Key::Char(c)) => text.push(c),
+            (Input::Number { text, min, .. }, Key::Char(c))
+                if c.is_ascii_digit() || (c == '-' && text.is_empty() && *min < 0) =>
+            {
+                text.push(c);
+            },
+            (Input::Text(text), Key::Backspace)
+            | (Input::Number { text, .. }, Key::Backspace) => {
+                text.pop();
+            },
+            (Input::Checkbox(checked), Key::Char(' ')) => *checked = !*checked,
+            (Input::Choice { selected, .. }, Key::Left) => {
+                *selected = selected.saturating_sub(1);
+            },
+            (Input::Choice { options, selected }, Key::Right)
+            | (Input::Choice { options, selected }, Key::Char(' ')) => {
+                *selected = (*selected + 1).min(options.len() - 1);
+            },
+            _ => (),
+        }
+    }
+
+    /// The value as it's shown in a column of `width` characters.
+    fn display(&self, width: usize, selected: bool) -> String
+    {
+        let text = match &self.input {
+            Input::Text(text) | Input::Number { text, .. } => {
+                let cursor = if selected { "_" } else { "" };
+                let len = text.chars().count();
+                // Keep the end of the text, where the cursor is, in view.
+                let skip = (len + cursor.len()).saturating_sub(width);
+                format!("{}{}", text.chars().skip(skip).collect::<String>(), cursor)
+            },
+            Input::Checkbox(checked) => {
+                if *checked { "[x]".to_string() } else { "[ ]".to_string() }
+            },
+            Input::Choice { options, selected } => format!("< {} >", options[*selected]),
+        };
+
+        format!("{:<width$}", text, width = width)
+    }
+}
+
+fn range_error(min: i64, max: i64) -> String
+{
+    format!("Must be between {} and {}.", min, max)
+}
+
+/// The value of a field once the form is submitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Text(String),
+    Number(i64),
+    Checkbox(bool),
+    /// Index of the picked option.
+    Choice(usize),
+}
+
+/// Values of a submitted form, looked up by the labels of the fields.
+///
+/// The typed getters panic if there's no such field or it's of another kind, since that's a
+/// mistake in the code that built the form.
+#[derive(Debug, Clone)]
+pub struct FormValues {
+    values: Vec<(String, Value)>,
+}
+
+impl FormValues {
+    pub fn get(&self, label: &str) -> Option<&Value>
+    {
+        self.values.iter()
+            .find(|(l, _)| l == label)
+            .map(|(_, v)| v)
+    }
+
+    pub fn text(&self, label: &str) -> &str
+    {
+        match self.expect(label) {
+            Value::Text(text) => text,
+            v => panic!("form field {:?} isn't text: {:?}", label, v),
+        }
+    }
+
+    pub fn number(&self, label: &str) -> i64
+    {
+        match self.expect(label) {
+            Value::Number(n) => *n,
+            v => panic!("form field {:?} isn't a number: {:?}", label, v),
+        }
+    }
+
+    pub fn checked(&self, label: &str) -> bool
+    {
+        match self.expect(label) {
+            Value::Checkbox(checked) => *checked,
+            v => panic!("form field {:?} isn't a checkbox: {:?}", label, v),
+        }
+    }
+
+    pub fn choice(&self, label: &str) -> usize
+    {
+        match self.expect(label) {
+            Value::Choice(i) => *i,
+            v => panic!("form field {:?} isn't a choice: {:?}", label, v),
+        }
+    }
+
+    fn expect(&self, label: &str) -> &Value
+    {
+        self.get(label).unwrap_or_else(|| panic!("no form field labelled {:?}", label))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FormOutput {
+    Submit(FormValues),
+    /// The user left the form with escape.
+    Cancel,
+}
+
+/// Labelled fields, one under the other, each with a line below it for its error message. The
+/// form has to be wide enough for the longest error message any field can show.
+///
+/// Tab and the up and down keys move between the fields, enter submits the form once every field
+/// is valid and escape cancels it.
+pub struct Form {
+    win: Window,
+    fields: Vec<Field>,
+    /// Error message of every field, shown under its value.
+    errors: Vec<Option<String>>,
+    label_width: usize,
+    selected: usize,
+    output: Option<FormOutput>,
+}
+
+impl Form {
+    /// Fails if `height` and `width` aren't enough to fit the fields.
+    pub fn new(
+        y: u32,
+        x: u32,
+        height: usize,
+        width: usize,
+        fields: Vec<Field>
+    ) -> Result<Self, FormError>
+    {
+        if fields.is_empty() {
+            return Err(FormError::NoFields);
+        }
+
+        let label_width = fields.iter().map(Field::label_width).max().unwrap_or(0);
+        let value_width = fields.iter().map(Field::min_width).max().unwrap_or(0);
+
+        let error_width = fields.iter().map(Field::error_width).max().unwrap_or(0);
+
+        let min_height = 1 + 2 * fields.len();
+        let row_width = (label_width + SPACER_WIDTH + value_width).max(error_width);
+        let min_width = MARGIN + row_width + MARGIN;
+        if height < min_height || width < min_width {
+            return Err(FormError::TooSmall { height: min_height, width: min_width });
+        }
+
+        let mut form = Self {
+            win: Window::new(y, x, height, width),
+            errors: vec![None; fields.len()],
+            fields,
+            label_width,
+            selected: 0,
+            output: None,
+        };
+        form.draw();
+
+        Ok(form)
+    }
+
+    fn value_x(&self) -> usize
+    {
+        MARGIN + self.label_width + SPACER_WIDTH
+    }
+
+    fn value_width(&self) -> usize
+    {
+        self.win.content_width().saturating_sub(self.value_x() + MARGIN)
+    }
+
+    /// Checks the field and remembers its error, if any. Returns whether the field is valid.
+    fn validate(&mut self, field: usize) -> bool
+    {
+        self.errors[field] = self.fields[field].validate().err();
+
+        self.errors[field].is_none()
+    }
+
+    fn values(&self) -> FormValues
+    {
+        FormValues {
+            values: self.fields.iter()
+                .map(|f| (f.label.content.clone(), f.value()))
+                .collect(),
+        }
+    }
+
+    fn draw(&mut self)
+    {
+        for i in 0..self.win.content_width() {
+            self.win.putc(0, i as u32, '=');
+        }
+
+        let value_x = self.value_x() as u32;
+        let width = self.value_width();
+
+        for i in 0..self.fields.len() {
+            let y = 1 + 2 * i as u32;
+            let field = &self.fields[i];
+
+            let label_x = MARGIN + self.label_width - field.label_width();
+            self.win.print(y, label_x as u32, &field.label);
+            self.win.print(y, value_x - SPACER_WIDTH as u32, ": ");
+
+            let selected = i == self.selected;
+            let value = field.display(width, selected);
+            if selected {
+                self.win.print(y, value_x, value.as_str().with_style(|s|
+                    s.text_style(TextStyle::UNDERLINE)
+                ));
+            } else {
+                self.win.print(y, value_x, value.as_str());
+            }
+
+            // The error goes across the whole row, the values are too narrow for most of them.
+            let error_width = self.win.content_width().saturating_sub(2 * MARGIN);
+            let error: String = self.errors[i].as_deref().unwrap_or("").chars()
+                .take(error_width)
+                .collect();
+            let error = format!("{:<width$}", error, width = error_width);
+            self.win.print(y + 1, MARGIN as u32, error.as_str().with_style(|s|
+                s.fg_color(Color::Rgb(ERROR_COLOR))
+            ));
+        }
+    }
+}
+
+impl Widget for Form {
+    fn share_inner(&self) -> InnerWidget
+    {
+        self.win.share_inner()
+    }
+}
+
+impl InteractiveWidget for Form {
+    fn process_event(&mut self, e: Event)
+    {
+        match e {
+            Event::Key(Key::Char('\t')) |
+            Event::Key(Key::Down) if self.selected + 1 != self.fields.len() => {
+                self.validate(self.selected);
+                self.selected += 1;
+            },
+            Event::Key(Key::BackTab) |
+            Event::Key(Key::Up) if self.selected != 0 => {
+                self.validate(self.selected);
+                self.selected -= 1;
+            },
+            // Already on the last or first field, these aren't for the field itself.
+            Event::Key(Key::Char('\t')) |
+            Event::Key(Key::Down) |
+            Event::Key(Key::BackTab) |
+            Event::Key(Key::Up) => (),
+            Event::Key(Key::Char('\n')) => {
+                let mut first_invalid = None;
+                for field in 0..self.fields.len() {
+                    if !self.validate(field) && first_invalid.is_none() {
+                        first_invalid = Some(field);
+                    }
+                }
+
+                match first_invalid {
+                    Some(field) => self.selected = field,
+                    None => self.output = Some(FormOutput::Submit(self.values())),
+                }
+            },
+            Event::Key(Key::Esc) => self.output = Some(FormOutput::Cancel),
+            Event::Key(key) => self.fields[self.selected].process_key(key),
+            _ => (),
+        }
+
+        self.draw();
+    }
+}
+
+impl OutputWidget<FormOutput> for Form {
+    fn try_get_output(&self) -> Option<FormOutput>
+    {
+        self.output.clone()
+    }
+
+    fn get_output(&self) -> Result<FormOutput, PoisonError<FormOutput>>
+    {
+        self.output.clone().ok_or(PoisonError::new(FormOutput::Cancel))
+    }
+}
+
+sub_impl_aligned!(Form, win);
+sub_impl_alignable!(Form, win, []);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn form(fields: Vec<Field>) -> Form
+    {
+        let height = 1 + 2 * fields.len();
+        Form::new(0, 0, height, 40, fields).unwrap()
+    }
+
+    fn press(form: &mut Form, keys: &[Key])
+    {
+        for key in keys {
+            form.process_event(Event::Key(*key));
+        }
+    }
+
+    fn type_text(form: &mut Form, text: &str)
+    {
+        for c in text.chars() {
+            form.process_event(Event::Key(Key::Char(c)));
+        }
+    }
+
+    fn submitted(form: &Form) -> Option<FormValues>
+    {
+        match form.try_get_output() {
+            Some(FormOutput::Submit(values)) => Some(values),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn text()
+    {
+        let mut form = form(vec![Field::text("Name", "Ay")]);
+        press(&mut form, &[Key::Backspace]);
+        type_text(&mut form, "yla");
+        press(&mut form, &[Key::Char('\n')]);
+
+        assert_eq!(submitted(&form).unwrap().text("Name"), "Ayla");
+    }
+
+    #[test]
+    fn number_range()
+    {
+        let mut form = form(vec![Field::number("Size", 1, 10, 5)]);
+        press(&mut form, &[Key::Backspace]);
+        type_text(&mut form, "-42");
+        press(&mut form, &[Key::Char('\n')]);
+
+        assert!(submitted(&form).is_none());
+        assert_eq!(form.errors[0].as_deref(), Some("Must be between 1 and 10."));
+
+        press(&mut form, &[Key::Backspace, Key::Char('\n')]);
+        assert_eq!(submitted(&form).unwrap().number("Size"), 4);
+    }
+
+    #[test]
+    fn negative_number()
+    {
+        let mut form = form(vec![Field::number("Offset", -5, 5, 0)]);
+        press(&mut form, &[Key::Backspace]);
+        type_text(&mut form, "-3");
+        press(&mut form, &[Key::Char('\n')]);
+
+        assert_eq!(submitted(&form).unwrap().number("Offset"), -3);
+    }
+
+    #[test]
+    fn choice()
+    {
+        let mut form = form(vec![Field::choice("Race", &["Human", "Elf", "Dwarf"], 0)]);
+
+        press(&mut form, &[Key::Left]);
+        assert_eq!(form.fields[0].value(), Value::Choice(0));
+        press(&mut form, &[Key::Right, Key::Right, Key::Right]);
+        assert_eq!(form.fields[0].value(), Value::Choice(2));
+        press(&mut form, &[Key::Left, Key::Char('\n')]);
+
+        assert_eq!(submitted(&form).unwrap().choice("Race"), 1);
+    }
+
+    #[test]
+    fn checkbox()
+    {
+        let mut form = form(vec![Field::checkbox("Clear", false)]);
+
+        press(&mut form, &[Key::Char(' ')]);
+        assert_eq!(form.fields[0].value(), Value::Checkbox(true));
+        press(&mut form, &[Key::Char(' '), Key::Char(' '), Key::Char('\n')]);
+
+        assert!(submitted(&form).unwrap().checked("Clear"));
+    }
+
+    #[test]
+    fn invalid_fields_block_submit()
+    {
+        let mut form = form(vec![
+            Field::text("Name", "").with_validator(Validator::Required),
+            Field::text("Title", "").with_validator(Validator::MaxLength(3)),
+        ]);
+        press(&mut form, &[Key::Char('\t')]);
+        type_text(&mut form, "Lord");
+        press(&mut form, &[Key::Char('\n')]);
+
+        assert!(submitted(&form).is_none());
+        assert_eq!(form.selected, 0);
+        assert_eq!(form.errors[0].as_deref(), Some("This field is required."));
+        assert_eq!(form.errors[1].as_deref(), Some("At most 3 characters."));
+
+        type_text(&mut form, "Ayla");
+        press(&mut form, &[Key::Char('\n')]);
+        assert!(submitted(&form).is_none());
+        assert_eq!(form.selected, 1);
+
+        press(&mut form, &[Key::Backspace, Key::Char('\n')]);
+        let values = submitted(&form).unwrap();
+        assert_eq!((values.text("Name"), values.text("Title")), ("Ayla", "Lor"));
+    }
+
+    #[test]
+    fn allowed_chars()
+    {
+        let mut form = form(vec![
+            Field::text("Name", "").with_validator(Validator::AllowedChars(char::is_alphabetic)),
+        ]);
+        type_text(&mut form, "R2");
+        press(&mut form, &[Key::Char('\n')]);
+
+        assert!(submitted(&form).is_none());
+        assert_eq!(form.errors[0].as_deref(), Some("'2' is not allowed."));
+    }
+
+    #[test]
+    fn custom_validator()
+    {
+        let even = Validator::custom(20, |v| {
+            match v.parse::<i64>() {
+                Ok(n) if n % 2 == 0 => Ok(()),
+                _ => Err("Must be even.".to_string()),
+            }
+        });
+        let mut form = form(vec![Field::number("Count", 0, 99, 3).with_validator(even)]);
+        press(&mut form, &[Key::Char('\n')]);
+
+        assert!(submitted(&form).is_none());
+        assert_eq!(form.errors[0].as_deref(), Some("Must be even."));
+
+        type_text(&mut form, "4");
+        press(&mut form, &[Key::Char('\n')]);
+        assert_eq!(submitted(&form).unwrap().number("Count"), 34);
+    }
+
+    #[test]
+    fn escape_cancels()
+    {
+        let mut form = form(vec![Field::text("Name", "").with_validator(Validator::Required)]);
+        press(&mut form, &[Key::Esc]);
+
+        assert!(matches!(form.try_get_output(), Some(FormOutput::Cancel)));
+    }
+
+    #[test]
+    fn size_check()
+    {
+        let fields = || vec![
+            Field::text("Name", "").with_validator(Validator::Required),
+            Field::number("Age", 1, 100, 20),
+        ];
+
+        // The range error is wider than the labels and values, and the other error.
+        let min_width = MARGIN + "Must be between 1 and 100.".len() + MARGIN;
+        assert!(matches!(
+            Form::new(0, 0, 4, 40, fields()),
+            Err(FormError::TooSmall { height: 5, width }) if width == min_width
+        ));
+        assert!(matches!(
+            Form::new(0, 0, 5, min_width - 1, fields()),
+            Err(FormError::TooSmall { .. })
+        ));
+        assert!(Form::new(0, 0, 5, min_width, fields()).is_ok());
+        assert!(matches!(Form::new(0, 0, 5, 40, Vec::new()), Err(FormError::NoFields)));
+
+        // Custom messages are as wide as the validator says.
+        let custom = Validator::custom(30, |_| Ok(()));
+        assert!(matches!(
+            Form::new(0, 0, 3, 35, vec![Field::text("Name", "").with_validator(custom)]),
+            Err(FormError::TooSmall { height: 3, width }) if width == MARGIN + 30 + MARGIN
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+use termion::event::{Event, Key};
+
+use cwinui::widget::{
+    Widget,
+    InteractiveWidget,
+    OutputWidget,
+    InnerWidget,
+    Window,
+};
+use cwinui::layout::Justify;
+use cwinui::widget::PoisonError;
+use cwinui::sub_impl_aligned;
+use cwinui::sub_impl_alignable;
+
+const HINTS: &str = "enter: use/equip  d: drop  esc: close";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryAction {
+    /// Use, equip or unequip the item at the index.
+    Use(usize),
+    Drop(usize),
+    Close,
+}
+
+/// List of the items the player carries, one of which is selected.
+pub struct InventoryMenu {
+    win: Window,
+    title: String,
+    items: Vec<String>,
+    selected: usize,
+    output: Option<InventoryAction>,
+}
+
+impl InventoryMenu {
+    pub fn new(y: u32, x: u32, title: &str, items: &[String]) -> Self
+    {
+        let width = items.iter()
+            .map(|i| i.chars().count() + 2)
+            .chain([title.chars().count(), HINTS.chars().count()])
+            .max()
+            .unwrap_or(0)
+            + 3 + 3;
+        // Title, separator, the items (or a line saying there are none), separator and hints.
+        let height = items.len().max(1) + 4;
+
+        let win = Window::new(y, x, height, width);
+
+        let mut ret = Self {
+            win,
+            title: title.to_string(),
+            items: items.to_vec(),
+            selected: 0,
+            output: None,
+        };
+        ret.draw();
+
+        ret
+    }
+
+    fn draw(&mut self)
+    {
+        let width = self.win.content_width();
+        let separator = "=".repeat(width);
+
+        self.win.printj(self.title.as_str(), Justify::HCentre(0));
+        self.win.print(1, 0, separator.as_str());
+
+        if self.items.is_empty() {
+            self.win.print(2, 3, "(nothing)");
+        }
+        for (i, item) in self.items.iter().enumerate() {
+            let marker = if i == self.selected { "* " } else { "  " };
+            let line = format!("{:<width$}", format!("{}{}", marker, item), width = width - 3);
+            self.win.print(i as u32 + 2, 3, line.as_str());
+        }
+
+        let bottom = self.items.len().max(1) as u32 + 2;
+        self.win.print(bottom, 0, separator.as_str());
+        self.win.printj(HINTS, Justify::HCentre(bottom + 1));
+    }
+}
+
+impl Widget for InventoryMenu {
+    fn share_inner(&self) -> InnerWidget
+    {
+        self.win.share_inner()
+    }
+}
+
+impl InteractiveWidget for InventoryMenu {
+    fn process_event(&mut self, e: Event)
+    {
+        let has_items = !self.items.is_empty();
+
+        match e {
+            Event::Key(Key::Down) | Event::Key(Key::Char('j'))
+                if self.selected + 1 < self.items.len() =>
+            {
+                self.selected += 1;
+            },
+            Event::Key(Key::Up) | Event::Key(Key::Char('k')) if self.selected != 0 => {
+                self.selected -= 1;
+            },
+            Event::Key(Key::Char('\n')) if has_items => {
+                self.output = Some(InventoryAction::Use(self.selected));
+            },
+            Event::Key(Key::Char('d')) if has_items => {
+                self.output = Some(InventoryAction::Drop(self.selected));
+            },
+            Event::Key(Key::Esc) | Event::Key(Key::Char('i')) => {
+                self.output = Some(InventoryAction::Close);
+            },
+            _ => (),
+        }
+
+        self.draw();
+    }
+}
+
+impl OutputWidget<InventoryAction> for InventoryMenu {
+    fn try_get_output(&self) -> Option<InventoryAction>
+    {
+        self.output
+    }
+
+    fn get_output(&self) -> Result<InventoryAction, PoisonError<InventoryAction>>
+    {
+        self.output.ok_or(PoisonError::new(InventoryAction::Close))
+    }
+}
+
+sub_impl_aligned!(InventoryMenu, win);
+sub_impl_alignable!(InventoryMenu, win, []);
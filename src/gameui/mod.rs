@@ -1,5 +1,11 @@
 mod startmenu;
-mod character_creation_form;
+mod form;
+mod inventory_menu;
+mod scroll_view;
+mod terminal;
 
 pub use startmenu::StartMenu;
-pub use character_creation_form::CharacterCreationForm;
+pub use form::{Form, Field, FormOutput, FormValues, Validator};
+pub use inventory_menu::{InventoryMenu, InventoryAction};
+pub use scroll_view::ScrollView;
+pub use terminal::Terminal;
@@ -0,0 +1,139 @@
+use cwinui::style::OwnedStyledText;
+use termion::event::{Event, Key};
+
+use cwinui::widget::{
+    Widget,
+    InteractiveWidget,
+    OutputWidget,
+    InnerWidget,
+    Window,
+};
+use cwinui::layout::Justify;
+use cwinui::widget::PoisonError;
+use cwinui::sub_impl_aligned;
+use cwinui::sub_impl_alignable;
+
+const HINTS: &str = "j/k: scroll  pgup/pgdn: page  esc: close";
+
+/// Read-only list of lines that can be scrolled through. Starts scrolled to the bottom.
+pub struct ScrollView {
+    win: Window,
+    title: String,
+    lines: Vec<OwnedStyledText>,
+    /// Index of the first line shown.
+    top: usize,
+    closed: bool,
+}
+
+impl ScrollView {
+    pub fn new(
+        y: u32,
+        x: u32,
+        height: usize,
+        width: usize,
+        title: &str,
+        lines: Vec<OwnedStyledText>
+    ) -> Self
+    {
+        let win = Window::new(y, x, height, width);
+
+        let mut ret = Self {
+            win,
+            title: title.to_string(),
+            lines,
+            top: 0,
+            closed: false,
+        };
+        ret.top = ret.max_top();
+        ret.draw();
+
+        ret
+    }
+
+    /// Number of lines that fit between the title and the hints.
+    fn page_len(&self) -> usize
+    {
+        self.win.content_height().saturating_sub(4).max(1)
+    }
+
+    fn max_top(&self) -> usize
+    {
+        self.lines.len().saturating_sub(self.page_len())
+    }
+
+    fn scroll(&mut self, by: i64)
+    {
+        let top = (self.top as i64 + by).clamp(0, self.max_top() as i64);
+        self.top = top as usize;
+    }
+
+    fn draw(&mut self)
+    {
+        let width = self.win.content_width();
+        let separator = "=".repeat(width);
+        let blank = " ".repeat(width);
+
+        self.win.print(0, 0, blank.as_str());
+        self.win.printj(self.title.as_str(), Justify::HCentre(0));
+        self.win.print(1, 0, separator.as_str());
+
+        for row in 0..self.page_len() {
+            let y = row as u32 + 2;
+            self.win.print(y, 0, blank.as_str());
+
+            if let Some(line) = self.lines.get(self.top + row) {
+                let mut line = line.clone();
+                line.content = line.content.chars().take(width).collect();
+                self.win.print(y, 0, &line);
+            }
+        }
+
+        let bottom = self.page_len() as u32 + 2;
+        self.win.print(bottom, 0, separator.as_str());
+        self.win.print(bottom + 1, 0, blank.as_str());
+        self.win.printj(HINTS, Justify::HCentre(bottom + 1));
+    }
+}
+
+impl Widget for ScrollView {
+    fn share_inner(&self) -> InnerWidget
+    {
+        self.win.share_inner()
+    }
+}
+
+impl InteractiveWidget for ScrollView {
+    fn process_event(&mut self, e: Event)
+    {
+        let page = self.page_len() as i64;
+
+        match e {
+            Event::Key(Key::Down) | Event::Key(Key::Char('j')) => self.scroll(1),
+            Event::Key(Key::Up) | Event::Key(Key::Char('k')) => self.scroll(-1),
+            Event::Key(Key::PageDown) | Event::Key(Key::Char(' ')) => self.scroll(page),
+            Event::Key(Key::PageUp) => self.scroll(-page),
+            Event::Key(Key::Home) => self.top = 0,
+            Event::Key(Key::End) => self.top = self.max_top(),
+            Event::Key(Key::Esc) | Event::Key(Key::Char('q')) => self.closed = true,
+            _ => (),
+        }
+
+        self.draw();
+    }
+}
+
+/// The output is ready once the view has been closed.
+impl OutputWidget<()> for ScrollView {
+    fn try_get_output(&self) -> Option<()>
+    {
+        if self.closed { Some(()) } else { None }
+    }
+
+    fn get_output(&self) -> Result<(), PoisonError<()>>
+    {
+        if self.closed { Ok(()) } else { Err(PoisonError::new(())) }
+    }
+}
+
+sub_impl_aligned!(ScrollView, win);
+sub_impl_alignable!(ScrollView, win, []);
@@ -0,0 +1,46 @@
+use cwinui::screen::Screen;
+use cwinui::widget::Widget;
+
+/// The screen of the game. `Screen` has a fixed size, so it's replaced by a new one when the
+/// terminal is resized.
+pub struct Terminal {
+    /// Only `None` while the screen is being replaced.
+    screen: Option<Screen>,
+}
+
+impl Terminal {
+    pub fn init(height: usize, width: usize) -> Self
+    {
+        Self {
+            screen: Some(Screen::init(height, width)),
+        }
+    }
+
+    /// Replaces the screen with one of the new size. Widgets have to be added again.
+    pub fn resize(&mut self, height: usize, width: usize)
+    {
+        // The old screen has to give the terminal back before the new one takes it over.
+        self.screen = None;
+        self.screen = Some(Screen::init(height, width));
+    }
+
+    pub fn add_widget<T: Widget>(&mut self, widget: &T)
+    {
+        self.screen().add_widget(widget);
+    }
+
+    pub fn rm_widget<T: Widget>(&mut self, widget: &T)
+    {
+        self.screen().rm_widget(widget);
+    }
+
+    pub fn refresh(&mut self)
+    {
+        self.screen().refresh();
+    }
+
+    fn screen(&mut self) -> &mut Screen
+    {
+        self.screen.as_mut().expect("the screen is being replaced")
+    }
+}
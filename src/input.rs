@@ -1,30 +1,124 @@
+//! Reading the terminal input. Everything that waits for the user goes through here, so that a
+//! broken input is reported as an error instead of being ignored or unwrapped.
+//!
+//! The input is read on its own thread, and terminal resizes (`SIGWINCH`) are caught on another
+//! one, so that waiting for a key can also be woken up by a resize.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Mutex, OnceLock};
+use signal_hook::consts::SIGWINCH;
+use signal_hook::iterator::Signals;
 use termion::input::TermRead;
 use termion::event::{Event, Key};
 
-pub fn getc() -> char
+use crate::error::{Error, Result};
+use crate::log;
+
+pub enum Input {
+    Event(Event),
+    /// The terminal was resized.
+    Resize,
+}
+
+enum Message {
+    Event(std::io::Result<Event>),
+    Resize,
+    Closed,
+}
+
+static MESSAGES: OnceLock<Mutex<Receiver<Message>>> = OnceLock::new();
+/// Set when a resize was skipped by `event`, or deferred, so that the next `next` reports it.
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+/// Waits for the next input event or resize.
+pub fn next() -> Result<Input>
 {
-    let mut ret = '\0';
+    if RESIZED.swap(false, Ordering::Relaxed) {
+        return Ok(Input::Resize);
+    }
 
-    for e in std::io::stdin().events() {
-        if let Event::Key(Key::Char(c)) = e.unwrap() {
-            ret = c;
-            break;
+    let messages = MESSAGES.get_or_init(|| Mutex::new(start()));
+    // Only a panic on another thread that was waiting for input poisons the lock, and the
+    // receiver is still fine then.
+    let messages = messages.lock().unwrap_or_else(|e| e.into_inner());
+
+    match messages.recv() {
+        Ok(Message::Event(e)) => e.map(Input::Event).map_err(Error::Input),
+        Ok(Message::Resize) => Ok(Input::Resize),
+        Ok(Message::Closed) | Err(_) => Err(Error::InputClosed),
+    }
+}
+
+/// Waits for the next input event. Resizes are left for the next call to `next`.
+pub fn event() -> Result<Event>
+{
+    loop {
+        match next()? {
+            Input::Event(e) => return Ok(e),
+            Input::Resize => defer_resize(),
         }
     }
+}
 
-    return ret;
+/// Makes the next call to `next` report a resize, for one that hasn't been dealt with
+/// everywhere yet.
+pub fn defer_resize()
+{
+    RESIZED.store(true, Ordering::Relaxed);
 }
 
-pub fn getkey() -> Key
+pub fn getc() -> Result<char>
 {
-    let mut ret = Key::Esc;
+    loop {
+        if let Event::Key(Key::Char(c)) = event()? {
+            return Ok(c);
+        }
+    }
+}
 
-    for e in std::io::stdin().events() {
-        if let Event::Key(k) = e.unwrap() {
-            ret = k;
-            break;
+pub fn getkey() -> Result<Key>
+{
+    loop {
+        if let Event::Key(k) = event()? {
+            return Ok(k);
         }
     }
+}
+
+/// Starts the threads that read the input and wait for resizes.
+fn start() -> Receiver<Message>
+{
+    let (sender, receiver) = mpsc::channel();
+
+    let events = sender.clone();
+    std::thread::spawn(move || {
+        for e in std::io::stdin().events() {
+            if events.send(Message::Event(e)).is_err() {
+                return;
+            }
+        }
+        let _ = events.send(Message::Closed);
+    });
+
+    // The game still works without this, it just doesn't notice resizes.
+    if let Err(e) = watch_resizes(sender) {
+        log::warning!("couldn't watch for terminal resizes: {}", e);
+    }
+
+    receiver
+}
+
+fn watch_resizes(sender: Sender<Message>) -> std::io::Result<()>
+{
+    let mut signals = Signals::new([SIGWINCH])?;
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            if sender.send(Message::Resize).is_err() {
+                return;
+            }
+        }
+    });
 
-    return ret;
+    Ok(())
 }
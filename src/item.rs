@@ -0,0 +1,294 @@
+//! Things that can be picked up, and the player's inventory.
+
+use crate::tile::RgbValue;
+
+/// Most items the pack can hold, not counting the equipped ones.
+pub const MAX_ITEMS: usize = 20;
+/// Most weight the player can carry, equipped items included.
+pub const MAX_WEIGHT: u32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Restores up to this many HP.
+    Heal(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    Weapon {
+        attack: i32,
+        accuracy: i32,
+    },
+    Armour {
+        defense: i32,
+    },
+    /// Used up when used.
+    Consumable(Effect),
+    /// Does nothing, but can be carried around.
+    Misc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Weapon,
+    Armour,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    pub name: String,
+    pub glyph: char,
+    pub color: RgbValue,
+    pub weight: u32,
+    pub kind: ItemKind,
+}
+
+impl Item {
+    /// The equipment slot the item goes into, `None` if it can't be equipped.
+    pub fn slot(&self) -> Option<Slot>
+    {
+        match self.kind {
+            ItemKind::Weapon { .. } => Some(Slot::Weapon),
+            ItemKind::Armour { .. } => Some(Slot::Armour),
+            _ => None,
+        }
+    }
+}
+
+struct Template {
+    name: &'static str,
+    glyph: char,
+    color: RgbValue,
+    weight: u32,
+    kind: ItemKind,
+}
+
+/// Items, by the id used in map item placements.
+static ITEMS: [(u32, Template); 7] = [
+    (1, Template {
+        name: "gold coin",
+        glyph: '$',
+        color: (0xff, 0xd7, 0x00),
+        weight: 1,
+        kind: ItemKind::Misc,
+    }),
+    (2, Template {
+        name: "rock",
+        glyph: '*',
+        color: (0x90, 0x90, 0x90),
+        weight: 20,
+        kind: ItemKind::Misc,
+    }),
+    (3, Template {
+        name: "dagger",
+        glyph: ')',
+        color: (0xc0, 0xc0, 0xd0),
+        weight: 10,
+        kind: ItemKind::Weapon { attack: 2, accuracy: 10 },
+    }),
+    (4, Template {
+        name: "short sword",
+        glyph: ')',
+        color: (0xd0, 0xd0, 0xe0),
+        weight: 30,
+        kind: ItemKind::Weapon { attack: 4, accuracy: 0 },
+    }),
+    (5, Template {
+        name: "leather armour",
+        glyph: '[',
+        color: (0xa0, 0x70, 0x40),
+        weight: 80,
+        kind: ItemKind::Armour { defense: 1 },
+    }),
+    (6, Template {
+        name: "chain mail",
+        glyph: '[',
+        color: (0xb0, 0xb0, 0xb0),
+        weight: 200,
+        kind: ItemKind::Armour { defense: 3 },
+    }),
+    (7, Template {
+        name: "healing potion",
+        glyph: '!',
+        color: (0xff, 0x40, 0x60),
+        weight: 5,
+        kind: ItemKind::Consumable(Effect::Heal(10)),
+    }),
+];
+
+pub const IDS: [u32; 7] = [1, 2, 3, 4, 5, 6, 7];
+
+pub fn by_id(id: u32) -> Option<Item>
+{
+    let (_, t) = ITEMS.iter().find(|(i, _)| *i == id)?;
+
+    Some(Item {
+        name: t.name.to_string(),
+        glyph: t.glyph,
+        color: t.color,
+        weight: t.weight,
+        kind: t.kind,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarryError {
+    /// The pack has `MAX_ITEMS` in it.
+    Full,
+    /// The item would go over `MAX_WEIGHT`.
+    TooHeavy,
+}
+
+/// Where an item is in the inventory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    Equipped(Slot),
+    Pack(usize),
+}
+
+/// The player's pack and equipment. Equipping an item moves it out of the pack into its slot.
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    pub pack: Vec<Item>,
+    pub weapon: Option<Item>,
+    pub armour: Option<Item>,
+}
+
+impl Inventory {
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    pub fn weight(&self) -> u32
+    {
+        self.pack.iter()
+            .chain(&self.weapon)
+            .chain(&self.armour)
+            .map(|i| i.weight)
+            .sum()
+    }
+
+    pub fn can_carry(&self, item: &Item) -> Result<(), CarryError>
+    {
+        if self.pack.len() >= MAX_ITEMS {
+            return Err(CarryError::Full);
+        }
+        if self.weight() + item.weight > MAX_WEIGHT {
+            return Err(CarryError::TooHeavy);
+        }
+
+        Ok(())
+    }
+
+    /// Puts the item in the pack, or gives it back if it doesn't fit.
+    pub fn add(&mut self, item: Item) -> Result<(), (Item, CarryError)>
+    {
+        match self.can_carry(&item) {
+            Ok(()) => {
+                self.pack.push(item);
+                Ok(())
+            },
+            Err(e) => Err((item, e)),
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Item>
+    {
+        if index >= self.pack.len() {
+            return None;
+        }
+
+        Some(self.pack.remove(index))
+    }
+
+    pub fn equipped(&self, slot: Slot) -> Option<&Item>
+    {
+        match slot {
+            Slot::Weapon => self.weapon.as_ref(),
+            Slot::Armour => self.armour.as_ref(),
+        }
+    }
+
+    fn slot_mut(&mut self, slot: Slot) -> &mut Option<Item>
+    {
+        match slot {
+            Slot::Weapon => &mut self.weapon,
+            Slot::Armour => &mut self.armour,
+        }
+    }
+
+    /// Equips the item at `index` of the pack. Whatever was in its slot goes back into the pack
+    /// in its place. Returns `false` if the item can't be equipped.
+    pub fn equip(&mut self, index: usize) -> bool
+    {
+        let slot = match self.pack.get(index).and_then(|i| i.slot()) {
+            Some(slot) => slot,
+            None => return false,
+        };
+
+        let item = self.pack.remove(index);
+        if let Some(old) = self.slot_mut(slot).replace(item) {
+            self.pack.insert(index, old);
+        }
+
+        true
+    }
+
+    /// Moves the item in `slot` back into the pack. Fails if the pack is full.
+    pub fn unequip(&mut self, slot: Slot) -> Result<(), CarryError>
+    {
+        if self.pack.len() >= MAX_ITEMS {
+            return Err(CarryError::Full);
+        }
+
+        if let Some(item) = self.slot_mut(slot).take() {
+            self.pack.push(item);
+        }
+
+        Ok(())
+    }
+
+    /// Everything carried, equipped items first.
+    pub fn entries(&self) -> Vec<(Entry, &Item)>
+    {
+        let equipped = [Slot::Weapon, Slot::Armour]
+            .into_iter()
+            .filter_map(|slot| Some((Entry::Equipped(slot), self.equipped(slot)?)));
+        let pack = self.pack.iter()
+            .enumerate()
+            .map(|(i, item)| (Entry::Pack(i), item));
+
+        equipped.chain(pack).collect()
+    }
+
+    /// Takes the item out of the inventory.
+    pub fn take(&mut self, entry: Entry) -> Option<Item>
+    {
+        match entry {
+            Entry::Equipped(slot) => self.slot_mut(slot).take(),
+            Entry::Pack(index) => self.remove(index),
+        }
+    }
+
+    /// Attack, defense and accuracy bonuses of the equipment.
+    pub fn bonuses(&self) -> (i32, i32, i32)
+    {
+        let mut attack = 0;
+        let mut defense = 0;
+        let mut accuracy = 0;
+
+        for item in self.weapon.iter().chain(&self.armour) {
+            match item.kind {
+                ItemKind::Weapon { attack: a, accuracy: acc } => {
+                    attack += a;
+                    accuracy += acc;
+                },
+                ItemKind::Armour { defense: d } => defense += d,
+                _ => (),
+            }
+        }
+
+        (attack, defense, accuracy)
+    }
+}
@@ -0,0 +1,376 @@
+//! Key bindings of the game commands.
+//!
+//! The defaults can be overridden in `$XDG_CONFIG_HOME/roggame/keys.conf` (or
+//! `~/.config/roggame/keys.conf`), which has one binding per line:
+//!
+//! ```text
+//! # Lines starting with '#' are comments, so that '#' can be bound like any other key.
+//! move_left = h left
+//! quit = C-q
+//! ```
+//!
+//! A command listed in the file loses its default keys. Keys are single characters or one of
+//! `space`, `tab`, `enter`, `esc`, `backspace`, `delete`, `insert`, `home`, `end`, `pageup`,
+//! `pagedown`, the arrows `left`, `right`, `up`, `down` and `f1` to `f12`, optionally prefixed
+//! by `C-` (control) or `A-` (alt).
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use termion::event::Key;
+
+use crate::direction::Direction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Move(Direction),
+    Wait,
+    Rest,
+    Pickup,
+    Inventory,
+    Messages,
+    Descend,
+    Ascend,
+    Quit,
+}
+
+/// Names of the commands in the config file.
+const COMMANDS: [(&str, Command); 16] = [
+    ("move_right", Command::Move(Direction::Right)),
+    ("move_left", Command::Move(Direction::Left)),
+    ("move_up", Command::Move(Direction::Up)),
+    ("move_down", Command::Move(Direction::Down)),
+    ("move_up_left", Command::Move(Direction::TopLeft)),
+    ("move_up_right", Command::Move(Direction::TopRight)),
+    ("move_down_left", Command::Move(Direction::DownLeft)),
+    ("move_down_right", Command::Move(Direction::DownRight)),
+    ("wait", Command::Wait),
+    ("rest", Command::Rest),
+    ("pickup", Command::Pickup),
+    ("inventory", Command::Inventory),
+    ("messages", Command::Messages),
+    ("descend", Command::Descend),
+    ("ascend", Command::Ascend),
+    ("quit", Command::Quit),
+];
+
+const DEFAULT_BINDINGS: [(Command, &[Key]); 16] = [
+    (Command::Move(Direction::Right), &[Key::Right, Key::Char('l')]),
+    (Command::Move(Direction::Left), &[Key::Left, Key::Char('h')]),
+    (Command::Move(Direction::Up), &[Key::Up, Key::Char('k')]),
+    (Command::Move(Direction::Down), &[Key::Down, Key::Char('j')]),
+    (Command::Move(Direction::TopLeft), &[Key::Char('y')]),
+    (Command::Move(Direction::TopRight), &[Key::Char('u')]),
+    (Command::Move(Direction::DownLeft), &[Key::Char('b')]),
+    (Command::Move(Direction::DownRight), &[Key::Char('n')]),
+    (Command::Wait, &[Key::Char('.')]),
+    (Command::Rest, &[Key::Char('R')]),
+    (Command::Pickup, &[Key::Char('g'), Key::Char(',')]),
+    (Command::Inventory, &[Key::Char('i')]),
+    (Command::Messages, &[Key::Char('m')]),
+    (Command::Descend, &[Key::Char('>')]),
+    (Command::Ascend, &[Key::Char('<')]),
+    (Command::Quit, &[Key::Char('q')]),
+];
+
+const NAMED_KEYS: [(&str, Key); 15] = [
+    ("space", Key::Char(' ')),
+    ("tab", Key::Char('\t')),
+    ("enter", Key::Char('\n')),
+    ("esc", Key::Esc),
+    ("backspace", Key::Backspace),
+    ("delete", Key::Delete),
+    ("insert", Key::Insert),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("up", Key::Up),
+    ("down", Key::Down),
+];
+
+/// Something wrong with the config file. None of them are fatal: the offending line or binding
+/// is skipped and the rest of the keymap is used.
+#[derive(Debug)]
+pub enum Problem {
+    Io(std::io::Error),
+    /// A line that isn't `command = keys`.
+    Syntax(usize),
+    UnknownCommand(usize, String),
+    UnknownKey(usize, String),
+    /// The key is bound to two commands. The second one is kept.
+    Conflict {
+        key: Key,
+        dropped: Command,
+        kept: Command,
+    },
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self {
+            Problem::Io(e) => write!(f, "couldn't read the key bindings: {}", e),
+            Problem::Syntax(line) => write!(f, "keys.conf:{}: expected `command = keys`", line),
+            Problem::UnknownCommand(line, name) => {
+                write!(f, "keys.conf:{}: unknown command: {}", line, name)
+            },
+            Problem::UnknownKey(line, name) => {
+                write!(f, "keys.conf:{}: unknown key: {}", line, name)
+            },
+            Problem::Conflict { key, dropped, kept } => {
+                write!(
+                    f,
+                    "{} is bound to both {} and {}, using {}",
+                    key_name(*key),
+                    command_name(*dropped),
+                    command_name(*kept),
+                    command_name(*kept)
+                )
+            },
+        }
+    }
+}
+
+pub struct Keymap {
+    bindings: HashMap<Key, Command>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self
+    {
+        let mut bindings = HashMap::new();
+        for (command, keys) in DEFAULT_BINDINGS {
+            for key in keys {
+                bindings.insert(*key, command);
+            }
+        }
+
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// Loads the user's keymap. A missing config file isn't a problem, the defaults are used.
+    pub fn load() -> (Self, Vec<Problem>)
+    {
+        let path = match config_path() {
+            Some(path) => path,
+            None => return (Self::default(), Vec::new()),
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Self::from_config(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (Self::default(), Vec::new()),
+            Err(e) => (Self::default(), vec![Problem::Io(e)]),
+        }
+    }
+
+    /// The default keymap with the bindings of the config file applied.
+    pub fn from_config(text: &str) -> (Self, Vec<Problem>)
+    {
+        let mut problems = Vec::new();
+        let mut overrides: Vec<(Command, Key)> = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line_nr = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, keys) = match line.split_once('=') {
+                Some((name, keys)) if !keys.trim().is_empty() => (name.trim(), keys),
+                _ => {
+                    problems.push(Problem::Syntax(line_nr));
+                    continue;
+                },
+            };
+
+            let command = match COMMANDS.iter().find(|(n, _)| *n == name) {
+                Some((_, command)) => *command,
+                None => {
+                    problems.push(Problem::UnknownCommand(line_nr, name.to_string()));
+                    continue;
+                },
+            };
+
+            for key in keys.split_whitespace() {
+                match parse_key(key) {
+                    Some(key) => overrides.push((command, key)),
+                    None => problems.push(Problem::UnknownKey(line_nr, key.to_string())),
+                }
+            }
+        }
+
+        // Default bindings of the commands in the config file are dropped, the other defaults
+        // stay unless the config file takes their keys.
+        let mut bindings: HashMap<Key, Command> = Keymap::default().bindings.into_iter()
+            .filter(|(_, command)| overrides.iter().all(|(c, _)| c != command))
+            .collect();
+
+        for (command, key) in overrides {
+            if let Some(old) = bindings.insert(key, command) {
+                if old != command {
+                    problems.push(Problem::Conflict { key, dropped: old, kept: command });
+                }
+            }
+        }
+
+        (Self { bindings }, problems)
+    }
+
+    pub fn command(&self, key: Key) -> Option<Command>
+    {
+        self.bindings.get(&key).copied()
+    }
+}
+
+/// Where the config file of the keymap is, if there's a config directory at all.
+pub fn config_path() -> Option<PathBuf>
+{
+    let dir = match std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(dir.join("roggame").join("keys.conf"))
+}
+
+fn command_name(command: Command) -> &'static str
+{
+    COMMANDS.iter()
+        .find(|(_, c)| *c == command)
+        .map(|(n, _)| *n)
+        .unwrap_or("?")
+}
+
+fn parse_key(s: &str) -> Option<Key>
+{
+    if let Some((_, key)) = NAMED_KEYS.iter().find(|(n, _)| *n == s) {
+        return Some(*key);
+    }
+
+    if let Some(n) = s.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+        return if (1..=12).contains(&n) { Some(Key::F(n)) } else { None };
+    }
+
+    let single = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+
+    if let Some(c) = s.strip_prefix("C-").and_then(single) {
+        return Some(Key::Ctrl(c));
+    }
+    if let Some(c) = s.strip_prefix("A-").and_then(single) {
+        return Some(Key::Alt(c));
+    }
+
+    single(s).map(Key::Char)
+}
+
+fn key_name(key: Key) -> String
+{
+    if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, k)| *k == key) {
+        return name.to_string();
+    }
+
+    match key {
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("C-{}", c),
+        Key::Alt(c) => format!("A-{}", c),
+        Key::F(n) => format!("f{}", n),
+        k => format!("{:?}", k),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keymap(config: &str) -> Keymap
+    {
+        let (keymap, problems) = Keymap::from_config(config);
+        assert!(problems.is_empty(), "{:?}", problems);
+
+        keymap
+    }
+
+    #[test]
+    fn defaults()
+    {
+        let keymap = keymap("");
+
+        assert_eq!(keymap.command(Key::Char('h')), Some(Command::Move(Direction::Left)));
+        assert_eq!(keymap.command(Key::Left), Some(Command::Move(Direction::Left)));
+        assert_eq!(keymap.command(Key::Char('q')), Some(Command::Quit));
+        assert_eq!(keymap.command(Key::Char('Z')), None);
+    }
+
+    #[test]
+    fn override_drops_the_default_keys()
+    {
+        let keymap = keymap("  # movement\n\nmove_left = a C-b f2\nquit=esc\n");
+
+        assert_eq!(keymap.command(Key::Char('a')), Some(Command::Move(Direction::Left)));
+        assert_eq!(keymap.command(Key::Ctrl('b')), Some(Command::Move(Direction::Left)));
+        assert_eq!(keymap.command(Key::F(2)), Some(Command::Move(Direction::Left)));
+        assert_eq!(keymap.command(Key::Char('h')), None);
+        assert_eq!(keymap.command(Key::Left), None);
+        assert_eq!(keymap.command(Key::Esc), Some(Command::Quit));
+        assert_eq!(keymap.command(Key::Char('q')), None);
+        // Commands that aren't in the config keep their defaults.
+        assert_eq!(keymap.command(Key::Char('l')), Some(Command::Move(Direction::Right)));
+    }
+
+    #[test]
+    fn hash_can_be_bound()
+    {
+        let keymap = keymap("# Not a binding = x\ninventory = # i\n");
+
+        assert_eq!(keymap.command(Key::Char('#')), Some(Command::Inventory));
+        assert_eq!(keymap.command(Key::Char('i')), Some(Command::Inventory));
+        assert_eq!(keymap.command(Key::Char('x')), None);
+    }
+
+    #[test]
+    fn conflicts()
+    {
+        let (keymap, problems) = Keymap::from_config("wait = q\n");
+
+        assert_eq!(keymap.command(Key::Char('q')), Some(Command::Wait));
+        assert!(matches!(
+            problems.as_slice(),
+            [Problem::Conflict {
+                key: Key::Char('q'),
+                dropped: Command::Quit,
+                kept: Command::Wait,
+            }]
+        ));
+    }
+
+    #[test]
+    fn bad_lines_are_skipped()
+    {
+        let config = "fly = f\nwait = . shift-x\nrest\npickup =\nascend = A-<\n";
+        let (keymap, problems) = Keymap::from_config(config);
+
+        assert!(matches!(
+            problems.as_slice(),
+            [
+                Problem::UnknownCommand(1, command),
+                Problem::UnknownKey(2, key),
+                Problem::Syntax(3),
+                Problem::Syntax(4),
+            ] if command == "fly" && key == "shift-x"
+        ));
+        assert_eq!(keymap.command(Key::Char('.')), Some(Command::Wait));
+        assert_eq!(keymap.command(Key::Char('g')), Some(Command::Pickup));
+        assert_eq!(keymap.command(Key::Alt('<')), Some(Command::Ascend));
+    }
+}
@@ -0,0 +1,176 @@
+//! Diagnostic log written to a file, since nothing can be printed while the game is on screen.
+//!
+//! Logging is off until `init` is called. Records look like
+//!
+//! ```text
+//! 2026-10-18 14:03:27.512 WARNING [roggame::game] couldn't save the game: ...
+//! ```
+//!
+//! and are written with the `error!`, `warning!`, `info!` and `debug!` macros, which tag them
+//! with the module they're in.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable with the path of the log file.
+pub const PATH_VAR: &str = "ROGGAME_LOG";
+/// Environment variable with the lowest level that's logged.
+pub const LEVEL_VAR: &str = "ROGGAME_LOG_LEVEL";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+
+impl Level {
+    pub const ALL: [Level; 4] = [Level::Error, Level::Warning, Level::Info, Level::Debug];
+
+    pub fn name(self) -> &'static str
+    {
+        match self {
+            Level::Error => "error",
+            Level::Warning => "warning",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Level>
+    {
+        Level::ALL.iter()
+            .copied()
+            .find(|l| l.name().eq_ignore_ascii_case(name))
+    }
+}
+
+struct Logger {
+    file: File,
+    level: Level,
+}
+
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+/// Starts logging records of `level` and above to the end of the file.
+pub fn init(path: &Path, level: Level) -> std::io::Result<()>
+{
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+
+    if let Ok(mut logger) = LOGGER.lock() {
+        *logger = Some(Logger { file, level });
+    }
+
+    Ok(())
+}
+
+/// Whether records of `level` end up in the log, for skipping work that's only done for the log.
+pub fn enabled(level: Level) -> bool
+{
+    match LOGGER.lock() {
+        Ok(logger) => logger.as_ref().is_some_and(|l| level <= l.level),
+        Err(_) => false,
+    }
+}
+
+/// Used by the logging macros.
+pub fn write(level: Level, module: &str, args: fmt::Arguments)
+{
+    // If the lock is poisoned, something panicked while logging and the log is as good as gone.
+    if let Ok(mut logger) = LOGGER.lock() {
+        write_to(&mut logger, level, module, args);
+    }
+}
+
+/// Like `write`, but gives up if the log is in use instead of waiting for it. For the panic hook,
+/// since the panic may have happened while this thread was writing to the log.
+pub fn try_write(level: Level, module: &str, args: fmt::Arguments)
+{
+    if let Ok(mut logger) = LOGGER.try_lock() {
+        write_to(&mut logger, level, module, args);
+    }
+}
+
+fn write_to(logger: &mut Option<Logger>, level: Level, module: &str, args: fmt::Arguments)
+{
+    if let Some(logger) = logger.as_mut().filter(|l| level <= l.level) {
+        // Failing to log isn't worth bothering the player with.
+        let _ = writeln!(
+            logger.file,
+            "{} {:<7} [{}] {}",
+            timestamp(),
+            level.name().to_uppercase(),
+            module,
+            args
+        );
+    }
+}
+
+/// The current UTC time, with milliseconds.
+fn timestamp() -> String
+{
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = now.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let time = secs % 86400;
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60,
+        now.subsec_millis()
+    )
+}
+
+/// `(year, month, day)` of the day that's `days` after 1970-01-01, see
+/// <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32)
+{
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::log::write($level, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::log::log!($crate::log::Level::Error, $($arg)*) };
+}
+
+macro_rules! warning {
+    ($($arg:tt)*) => { $crate::log::log!($crate::log::Level::Warning, $($arg)*) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::log::log!($crate::log::Level::Info, $($arg)*) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::log::log!($crate::log::Level::Debug, $($arg)*) };
+}
+
+pub(crate) use {log, error, warning, info, debug};
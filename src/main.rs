@@ -2,12 +2,116 @@ mod game;
 mod map;
 mod player;
 mod input;
-mod gameui;
+// Public, so that every widget counts as used, not only the parts the game needs so far.
+pub mod gameui;
+mod serial;
+mod tile;
+mod camera;
+mod fov;
+mod rng;
+mod mapgen;
+mod dungeon;
+mod entity;
+mod turn;
+mod combat;
+mod direction;
+mod path;
+mod ai;
+mod item;
+mod messages;
+mod character;
+mod keymap;
+mod cli;
+mod error;
+mod log;
+
+use std::path::PathBuf;
+use std::sync::Mutex;
 
 use game::Game;
+use cli::Action;
+
+/// Message of the last panic, printed once the terminal is restored.
+static PANIC_MESSAGE: Mutex<Option<String>> = Mutex::new(None);
 
 fn main()
 {
-    let mut game = Game::new();
-    game.run();
+    let options = match cli::parse(std::env::args().skip(1)) {
+        Ok(Action::Run(options)) => options,
+        Ok(Action::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        },
+        Ok(Action::Version) => {
+            println!("roggame {}", env!("CARGO_PKG_VERSION"));
+            return;
+        },
+        Err(e) => {
+            eprintln!("roggame: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        },
+    };
+
+    if let Some(map) = &options.map {
+        // The editor starts a new map if the file doesn't exist.
+        if !options.editor && !map.is_file() {
+            eprintln!("roggame: no such map file: {}", map.display());
+            std::process::exit(1);
+        }
+    }
+
+    if let Err(e) = init_log(&options) {
+        eprintln!("roggame: couldn't open the log file: {}", e);
+        std::process::exit(1);
+    }
+    log::info!("roggame {} started", env!("CARGO_PKG_VERSION"));
+
+    // Anything printed while the game is running ends up on its screen and is lost, so the
+    // panic message is kept until the game is dropped, which restores the terminal.
+    std::panic::set_hook(Box::new(|info| {
+        log::try_write(log::Level::Error, module_path!(), format_args!("{}", info));
+        if let Ok(mut msg) = PANIC_MESSAGE.lock() {
+            *msg = Some(info.to_string());
+        }
+    }));
+
+    let result = std::panic::catch_unwind(|| {
+        let mut game = Game::new(&options)?;
+        game.run(&options)
+    });
+
+    match result {
+        Ok(Ok(())) => log::info!("roggame exited"),
+        Ok(Err(e)) => {
+            log::error!("{}", e);
+            eprintln!("roggame: {}", e);
+            std::process::exit(1);
+        },
+        Err(_) => {
+            let msg = PANIC_MESSAGE.lock().ok()
+                .and_then(|mut msg| msg.take())
+                .unwrap_or_else(|| "unknown error".to_string());
+            eprintln!("roggame crashed: {}", msg);
+            std::process::exit(101);
+        },
+    }
+}
+
+/// Starts the log if a file is given on the command line or in the environment.
+fn init_log(options: &cli::Options) -> std::io::Result<()>
+{
+    let path = options.log.clone().or_else(|| {
+        std::env::var_os(log::PATH_VAR)
+            .filter(|p| !p.is_empty())
+            .map(PathBuf::from)
+    });
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let level = options.log_level
+        .or_else(|| std::env::var(log::LEVEL_VAR).ok().and_then(|l| log::Level::from_name(&l)))
+        .unwrap_or(log::Level::Info);
+
+    log::init(&path, level)
 }
@@ -1,12 +1,98 @@
-use std::io::Read;
+//! Maps and the map file format.
+//!
+//! A map file is a `serial::Container` (see `serial.rs`) with the `RGMP` magic and the following
+//! sections. Strings are length-prefixed UTF-8, positions are `y: u32, x: u32`.
+//!
+//! ```text
+//! META  name: str, author: str, spawn: pos         (optional)
+//! TERR  height: u32, width: u32, cells: [u8; height * width], row-major
+//! ITEM  count: u32, { pos, id: u32 } * count       (optional)
+//! SPWN  count: u32, { pos, id: u32 } * count       (optional)
+//! ```
+//!
+//! Files without the magic are read as legacy maps: `height: u32, width: u32`, 16 reserved
+//! bytes, then the cells.
+//!
+//! Maps can also be written as text, for authoring in an editor. The text form is a header of
+//! `key: value` lines, a `---` separator, and then the rows of the grid, one character per cell:
+//!
+//! ```text
+//! name: Cellar
+//! author: jonny
+//! spawn: 2 3
+//! item: 1 1 4
+//! monster: 3 5 1
+//! ---
+//! #######
+//! #.....#
+//! #######
+//! ```
+//!
+//! Rows shorter than the widest row are padded with spaces. Lines starting with `;` in the
+//! header are comments. Cells that aren't printable ASCII are written as `?` in the grid and
+//! given in the header as `cell: y x value`.
+
+use std::io::{Read, Write};
 
 use cwinui::pos;
+use crate::serial::{self, Container, Decoder, Encoder};
+use crate::player::Pos;
+use crate::log;
+
+const MAGIC: [u8; 4] = *b"RGMP";
+const VERSION: u32 = 1;
+
+const META_SECTION: [u8; 4] = *b"META";
+const TERRAIN_SECTION: [u8; 4] = *b"TERR";
+const ITEM_SECTION: [u8; 4] = *b"ITEM";
+const SPAWN_SECTION: [u8; 4] = *b"SPWN";
+
+const LEGACY_RESERVED: usize = 16;
+
+const TEXT_SEPARATOR: &str = "---";
+/// Stands in the text grid for a cell that's given in the header.
+const TEXT_ESCAPED_CELL: char = '?';
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct Cell(pub u8);
 
+/// Error in a text map. `line` is 1-based.
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct MapMeta {
+    pub name: String,
+    pub author: String,
+    pub spawn: Pos,
+}
+
+/// Something placed on the map at load time, e.g. an item or a monster spawn.
+#[derive(Debug, Clone, Copy)]
+pub struct Placement {
+    pub pos: Pos,
+    pub id: u32,
+}
+
 pub struct Map {
     pub grid: Vec<Cell>,
+    pub meta: MapMeta,
+    pub items: Vec<Placement>,
+    pub spawns: Vec<Placement>,
+    /// Cells the player has seen. Part of the game state rather than the map file.
+    explored: Vec<bool>,
     height: usize,
     width: usize,
 }
@@ -18,10 +104,14 @@ impl Map {
             height: height as usize,
             width: width as usize,
             grid: Vec::with_capacity((height * width) as usize),
+            meta: MapMeta::default(),
+            items: Vec::new(),
+            spawns: Vec::new(),
+            explored: Vec::new(),
         }
     }
 
-    pub fn from_reader<R: Read>(reader: &mut R) -> Result<Self, std::io::Error>
+    pub fn from_reader<R: Read>(reader: &mut R) -> Result<Self, serial::Error>
     {
         let mut m = Self::new(0, 0);
         m.load(reader)?;
@@ -39,39 +129,565 @@ impl Map {
         self.width
     }
 
-    pub fn load<R: Read>(&mut self, reader: &mut R) -> Result <(), std::io::Error>
+    pub fn in_bounds(&self, pos: Pos) -> bool
+    {
+        (pos.y as usize) < self.height && (pos.x as usize) < self.width
+    }
+
+    pub fn cell(&self, pos: Pos) -> Cell
+    {
+        self.grid[pos!(self.width, pos.y as usize, pos.x as usize)]
+    }
+
+    pub fn set_cell(&mut self, pos: Pos, cell: Cell)
     {
-        let mut u32_buf = [u8::default(); 4];
+        self.grid[pos!(self.width, pos.y as usize, pos.x as usize)] = cell;
+    }
 
-        reader.read_exact(&mut u32_buf)?;
-        let height = u32::from_le_bytes(u32_buf) as usize;
-        reader.read_exact(&mut u32_buf)?;
-        let width = u32::from_le_bytes(u32_buf) as usize;
+    /// First position of `cell`, in row-major order.
+    pub fn find(&self, cell: Cell) -> Option<Pos>
+    {
+        let i = self.grid.iter().position(|c| *c == cell)?;
 
+        Some(Pos {
+            y: (i / self.width) as u32,
+            x: (i % self.width) as u32,
+        })
+    }
+
+    pub fn is_explored(&self, pos: Pos) -> bool
+    {
+        self.explored.get(pos!(self.width, pos.y as usize, pos.x as usize))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn set_explored(&mut self, pos: Pos)
+    {
+        let i = pos!(self.width, pos.y as usize, pos.x as usize);
+        if let Some(e) = self.explored.get_mut(i) {
+            *e = true;
+        }
+    }
+
+    pub fn explored(&self) -> &[bool]
+    {
+        &self.explored
+    }
+
+    /// Replaces the explored layer. Returns `false` if it doesn't match the size of the map.
+    pub fn set_explored_layer(&mut self, explored: Vec<bool>) -> bool
+    {
+        if explored.len() != self.grid.len() {
+            return false;
+        }
+
+        self.explored = explored;
+
+        true
+    }
+
+    /// Resizes the map, keeping the top-left part of the grid. New cells are set to `fill`.
+    /// Placements that no longer fit are dropped and the spawn point is clamped to the new size.
+    pub fn resize(&mut self, height: usize, width: usize, fill: Cell)
+    {
+        let mut grid = vec![fill; height * width];
+        for y in 0..self.height.min(height) {
+            for x in 0..self.width.min(width) {
+                grid[pos!(width, y, x)] = self.grid[pos!(self.width, y, x)];
+            }
+        }
+
+        self.grid = grid;
         self.height = height;
         self.width = width;
+        self.explored = vec![false; self.grid.len()];
+
+        self.meta.spawn.y = self.meta.spawn.y.min(height.saturating_sub(1) as u32);
+        self.meta.spawn.x = self.meta.spawn.x.min(width.saturating_sub(1) as u32);
+        self.items.retain(|p| (p.pos.y as usize) < height && (p.pos.x as usize) < width);
+        self.spawns.retain(|p| (p.pos.y as usize) < height && (p.pos.x as usize) < width);
+    }
+
+    pub fn load<R: Read>(&mut self, reader: &mut R) -> Result<(), serial::Error>
+    {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        if bytes.starts_with(&MAGIC) {
+            self.load_container(&Container::from_bytes(MAGIC, &bytes)?)
+        } else {
+            self.load_legacy(&bytes)
+        }
+    }
+
+    /// Writes the map in the format read by `load`.
+    pub fn save<W: Write>(&self, writer: &mut W) -> Result<(), serial::Error>
+    {
+        let mut container = Container::new(VERSION);
+
+        let mut meta = Encoder::new();
+        meta.put_str(&self.meta.name);
+        meta.put_str(&self.meta.author);
+        put_pos(&mut meta, self.meta.spawn);
+        container.add_section(META_SECTION, meta);
+
+        let mut terrain = Encoder::new();
+        terrain.put_u32(self.height as u32);
+        terrain.put_u32(self.width as u32);
+        for c in &self.grid {
+            terrain.put_u8(c.0);
+        }
+        container.add_section(TERRAIN_SECTION, terrain);
+
+        container.add_section(ITEM_SECTION, encode_placements(&self.items));
+        container.add_section(SPAWN_SECTION, encode_placements(&self.spawns));
+
+        container.write(MAGIC, writer)
+    }
+
+    pub fn to_text(&self) -> String
+    {
+        let mut text = String::new();
+
+        text.push_str(&format!("name: {}\n", self.meta.name));
+        text.push_str(&format!("author: {}\n", self.meta.author));
+        text.push_str(&format!("spawn: {} {}\n", self.meta.spawn.y, self.meta.spawn.x));
+        for p in &self.items {
+            text.push_str(&format!("item: {} {} {}\n", p.pos.y, p.pos.x, p.id));
+        }
+        for p in &self.spawns {
+            text.push_str(&format!("monster: {} {} {}\n", p.pos.y, p.pos.x, p.id));
+        }
+        for (i, c) in self.grid.iter().enumerate() {
+            if !is_text_cell(*c) {
+                text.push_str(&format!("cell: {} {} {}\n", i / self.width, i % self.width, c.0));
+            }
+        }
+        text.push_str(TEXT_SEPARATOR);
+        text.push('\n');
+
+        for row in self.grid.chunks(self.width.max(1)) {
+            text.extend(row.iter().map(|c| {
+                if is_text_cell(*c) { c.0 as char } else { TEXT_ESCAPED_CELL }
+            }));
+            text.push('\n');
+        }
+
+        text
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, ParseError>
+    {
+        let mut lines = text.lines().enumerate();
+        let mut meta = MapMeta::default();
+        let mut spawn = None;
+        let mut items = Vec::new();
+        let mut spawns = Vec::new();
+        let mut cells = Vec::new();
+
+        let mut separator_found = false;
+        for (i, line) in lines.by_ref() {
+            let err = |reason| ParseError { line: i + 1, reason };
+
+            if line.trim_end() == TEXT_SEPARATOR {
+                separator_found = true;
+                break;
+            }
+            if line.trim().is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            let (key, value) = line.split_once(':')
+                .ok_or_else(|| err("expected `key: value`"))?;
+            let value = value.trim();
+
+            match key.trim() {
+                "name" => meta.name = value.to_string(),
+                "author" => meta.author = value.to_string(),
+                "spawn" => {
+                    let nums = parse_numbers(value, 2)
+                        .ok_or_else(|| err("expected `spawn: y x`"))?;
+                    spawn = Some((i + 1, Pos { y: nums[0], x: nums[1] }));
+                },
+                "item" | "monster" => {
+                    let nums = parse_numbers(value, 3)
+                        .ok_or_else(|| err("expected `y x id`"))?;
+                    let placement = Placement {
+                        pos: Pos { y: nums[0], x: nums[1] },
+                        id: nums[2],
+                    };
+                    if key.trim() == "item" {
+                        items.push((i + 1, placement));
+                    } else {
+                        spawns.push((i + 1, placement));
+                    }
+                },
+                "cell" => {
+                    let nums = parse_numbers(value, 3)
+                        .ok_or_else(|| err("expected `cell: y x value`"))?;
+                    let value = u8::try_from(nums[2])
+                        .map_err(|_| err("cell value is larger than 255"))?;
+                    cells.push((i + 1, Pos { y: nums[0], x: nums[1] }, Cell(value)));
+                },
+                _ => return Err(err("unknown key")),
+            }
+        }
+
+        if !separator_found {
+            let line = text.lines().count();
+            return Err(ParseError { line, reason: "missing `---` separator" });
+        }
+
+        let mut rows = Vec::new();
+        for (i, line) in lines {
+            if !line.is_ascii() {
+                return Err(ParseError { line: i + 1, reason: "non-ASCII character in the grid" });
+            }
+            rows.push(line.as_bytes());
+        }
+        while rows.last().is_some_and(|r| r.is_empty()) {
+            rows.pop();
+        }
+        if rows.is_empty() {
+            let line = text.lines().count();
+            return Err(ParseError { line, reason: "the grid is empty" });
+        }
+
+        let height = rows.len();
+        let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+
+        let mut map = Self::new(height as u32, width as u32);
+        for row in &rows {
+            map.grid.extend(row.iter().map(|b| Cell(*b)));
+            map.grid.extend(std::iter::repeat_n(Cell(b' '), width - row.len()));
+        }
+        map.explored = vec![false; map.grid.len()];
+
+        for (line, pos, cell) in cells {
+            if !map.in_bounds(pos) {
+                return Err(ParseError { line, reason: "cell is outside of the map" });
+            }
+            map.set_cell(pos, cell);
+        }
+
+        map.meta = match spawn {
+            Some((line, pos)) => {
+                if !map.in_bounds(pos) {
+                    return Err(ParseError { line, reason: "spawn point is outside of the map" });
+                }
+                MapMeta { spawn: pos, ..meta }
+            },
+            None => MapMeta { spawn: map.centre(), ..meta },
+        };
+
+        for (line, placement) in &items {
+            if !map.in_bounds(placement.pos) {
+                return Err(ParseError { line: *line, reason: "item is outside of the map" });
+            }
+            map.items.push(*placement);
+        }
+        for (line, placement) in &spawns {
+            if !map.in_bounds(placement.pos) {
+                return Err(ParseError { line: *line, reason: "monster is outside of the map" });
+            }
+            map.spawns.push(*placement);
+        }
+
+        Ok(map)
+    }
+
+    fn load_container(&mut self, container: &Container) -> Result<(), serial::Error>
+    {
+        if container.version != VERSION {
+            return Err(serial::Error::UnsupportedVersion(container.version));
+        }
 
-        reader.read_exact(&mut <[u8; 16]>::default())?;
+        let mut terrain = container.require_section(TERRAIN_SECTION)?;
+        let height = terrain.get_u32()? as usize;
+        let width = terrain.get_u32()? as usize;
+        self.set_grid(height, width, terrain.get_bytes(grid_len(height, width)?)?);
 
-        let mut buf = vec![u8::default(); height * width];
-        reader.read_exact(&mut buf)?;
+        self.meta = match container.section(META_SECTION) {
+            Some(mut meta) => MapMeta {
+                name: meta.get_str()?,
+                author: meta.get_str()?,
+                spawn: get_pos(&mut meta)?,
+            },
+            None => MapMeta {
+                spawn: self.centre(),
+                ..MapMeta::default()
+            },
+        };
+        if !self.in_bounds(self.meta.spawn) {
+            return Err(serial::Error::Corrupt("spawn point is outside of the map"));
+        }
+
+        self.items = match container.section(ITEM_SECTION) {
+            Some(mut dec) => self.get_placements(&mut dec)?,
+            None => Vec::new(),
+        };
+        self.spawns = match container.section(SPAWN_SECTION) {
+            Some(mut dec) => self.get_placements(&mut dec)?,
+            None => Vec::new(),
+        };
+
+        Ok(())
+    }
+
+    fn load_legacy(&mut self, bytes: &[u8]) -> Result<(), serial::Error>
+    {
+        let mut dec = Decoder::new(bytes);
 
-        self.grid.resize(height * width, Cell::default());
+        let height = dec.get_u32()? as usize;
+        let width = dec.get_u32()? as usize;
+        dec.get_bytes(LEGACY_RESERVED)?;
+        self.set_grid(height, width, dec.get_bytes(grid_len(height, width)?)?);
 
-        for (c, b) in std::iter::zip(&mut self.grid, &buf) {
-            *c = Cell { 0: *b };
+        self.meta = MapMeta {
+            spawn: self.centre(),
+            ..MapMeta::default()
+        };
+        if !self.in_bounds(self.meta.spawn) {
+            return Err(serial::Error::Corrupt("spawn point is outside of the map"));
         }
+        self.items.clear();
+        self.spawns.clear();
 
         Ok(())
     }
 
+    fn set_grid(&mut self, height: usize, width: usize, cells: &[u8])
+    {
+        self.height = height;
+        self.width = width;
+        self.grid.clear();
+        self.grid.extend(cells.iter().map(|b| Cell(*b)));
+        self.explored = vec![false; self.grid.len()];
+    }
+
+    fn get_placements(&self, dec: &mut Decoder) -> Result<Vec<Placement>, serial::Error>
+    {
+        let count = dec.get_u32()?;
+        let mut placements = Vec::new();
+
+        for _ in 0..count {
+            let placement = Placement {
+                pos: get_pos(dec)?,
+                id: dec.get_u32()?,
+            };
+            if !self.in_bounds(placement.pos) {
+                return Err(serial::Error::Corrupt("placement is outside of the map"));
+            }
+
+            placements.push(placement);
+        }
+
+        Ok(placements)
+    }
+
+    fn centre(&self) -> Pos
+    {
+        Pos {
+            y: self.height as u32 / 2,
+            x: self.width as u32 / 2,
+        }
+    }
+
+    /// Writes the cells to the log, for debugging.
     pub fn dump(&self)
     {
+        if !log::enabled(log::Level::Debug) {
+            return;
+        }
+
+        let mut text = String::with_capacity((self.width + 1) * self.height);
         for y in 0..self.height {
+            text.push('\n');
             for x in 0..self.width {
-                eprint!("{}", self.grid[pos!(self.width, y, x)].0 as char);
+                text.push(self.grid[pos!(self.width, y, x)].0 as char);
             }
-            eprintln!("");
+        }
+
+        log::debug!("map {}x{}:{}", self.height, self.width, text);
+    }
+}
+
+fn grid_len(height: usize, width: usize) -> Result<usize, serial::Error>
+{
+    height.checked_mul(width).ok_or(serial::Error::Corrupt("map dimensions are too large"))
+}
+
+fn get_pos(dec: &mut Decoder) -> Result<Pos, serial::Error>
+{
+    Ok(Pos {
+        y: dec.get_u32()?,
+        x: dec.get_u32()?,
+    })
+}
+
+fn put_pos(enc: &mut Encoder, pos: Pos)
+{
+    enc.put_u32(pos.y);
+    enc.put_u32(pos.x);
+}
+
+fn encode_placements(placements: &[Placement]) -> Encoder
+{
+    let mut enc = Encoder::new();
+    enc.put_u32(placements.len() as u32);
+    for p in placements {
+        put_pos(&mut enc, p.pos);
+        enc.put_u32(p.id);
+    }
+
+    enc
+}
+
+/// Whether the cell can be written as itself in the text grid.
+fn is_text_cell(cell: Cell) -> bool
+{
+    (b' '..=b'~').contains(&cell.0)
+}
+
+/// Parses exactly `count` whitespace-separated numbers.
+fn parse_numbers(s: &str, count: usize) -> Option<Vec<u32>>
+{
+    let nums: Vec<u32> = s.split_whitespace()
+        .map(|n| n.parse().ok())
+        .collect::<Option<_>>()?;
+
+    if nums.len() == count { Some(nums) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Map
+    {
+        let mut map = Map::new(0, 0);
+        map.resize(3, 4, Cell(b'.'));
+        map.set_cell(Pos { y: 0, x: 0 }, Cell(b'#'));
+        map.set_cell(Pos { y: 1, x: 2 }, Cell(b'+'));
+        map.meta = MapMeta {
+            name: "Cellar".to_string(),
+            author: "jonny".to_string(),
+            spawn: Pos { y: 1, x: 1 },
+        };
+        map.items.push(Placement { pos: Pos { y: 2, x: 3 }, id: 4 });
+        map.spawns.push(Placement { pos: Pos { y: 0, x: 1 }, id: 7 });
+
+        map
+    }
+
+    fn assert_same(a: &Map, b: &Map)
+    {
+        assert_eq!((a.height(), a.width()), (b.height(), b.width()));
+        assert_eq!(a.grid, b.grid);
+        assert_eq!(a.meta.name, b.meta.name);
+        assert_eq!(a.meta.author, b.meta.author);
+        assert_eq!(a.meta.spawn, b.meta.spawn);
+        let placements = |p: &[Placement]| p.iter().map(|p| (p.pos, p.id)).collect::<Vec<_>>();
+        assert_eq!(placements(&a.items), placements(&b.items));
+        assert_eq!(placements(&a.spawns), placements(&b.spawns));
+    }
+
+    fn parse_error(text: &str) -> ParseError
+    {
+        match Map::from_text(text) {
+            Ok(_) => panic!("{:?} parsed", text),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn binary_round_trip()
+    {
+        let map = sample();
+        let mut bytes = Vec::new();
+        map.save(&mut bytes).unwrap();
+
+        assert_same(&map, &Map::from_reader(&mut bytes.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn legacy_maps()
+    {
+        let legacy = |height: u32, width: u32| {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(&height.to_le_bytes());
+            bytes.extend_from_slice(&width.to_le_bytes());
+            bytes.extend_from_slice(&[0; LEGACY_RESERVED]);
+            bytes.extend(std::iter::repeat_n(b'.', (height * width) as usize));
+            Map::from_reader(&mut bytes.as_slice())
+        };
+
+        let map = legacy(2, 3).unwrap();
+        assert_eq!((map.height(), map.width()), (2, 3));
+        assert_eq!(map.meta.spawn, Pos { y: 1, x: 1 });
+
+        for (height, width) in [(0, 0), (0, 3), (2, 0)] {
+            assert!(matches!(
+                legacy(height, width),
+                Err(serial::Error::Corrupt("spawn point is outside of the map"))
+            ));
+        }
+    }
+
+    #[test]
+    fn text_round_trip()
+    {
+        let map = sample();
+
+        assert_same(&map, &Map::from_text(&map.to_text()).unwrap());
+    }
+
+    #[test]
+    fn text_round_trip_escapes_cells()
+    {
+        let mut map = sample();
+        map.set_cell(Pos { y: 0, x: 2 }, Cell(b'\n'));
+        map.set_cell(Pos { y: 1, x: 3 }, Cell(b'\r'));
+        map.set_cell(Pos { y: 2, x: 0 }, Cell(0xb0));
+        map.set_cell(Pos { y: 2, x: 1 }, Cell(b' '));
+        let text = map.to_text();
+
+        assert!(text.is_ascii());
+        assert_same(&map, &Map::from_text(&text).unwrap());
+    }
+
+    #[test]
+    fn text_pads_short_rows()
+    {
+        let map = Map::from_text("---\n###\n#\n").unwrap();
+
+        assert_eq!((map.height(), map.width()), (2, 3));
+        assert_eq!(map.cell(Pos { y: 1, x: 2 }), Cell(b' '));
+        assert_eq!(map.meta.spawn, Pos { y: 1, x: 1 });
+    }
+
+    #[test]
+    fn text_errors()
+    {
+        let cases = [
+            ("name: x\nauthor: y\n", 2, "missing `---` separator"),
+            ("name x\n---\n#\n", 1, "expected `key: value`"),
+            ("colour: red\n---\n#\n", 1, "unknown key"),
+            ("spawn: 1\n---\n#\n", 1, "expected `spawn: y x`"),
+            ("\nitem: 0 0\n---\n#\n", 2, "expected `y x id`"),
+            ("cell: 0 0 256\n---\n#\n", 1, "cell value is larger than 255"),
+            ("---\n#\n#é\n", 3, "non-ASCII character in the grid"),
+            ("name: x\n---\n\n", 3, "the grid is empty"),
+            ("spawn: 0 5\n---\n#\n", 1, "spawn point is outside of the map"),
+            ("item: 1 0 3\n---\n#\n", 1, "item is outside of the map"),
+            ("monster: 0 1 3\n---\n#\n", 1, "monster is outside of the map"),
+            ("cell: 2 2 0\n---\n#\n", 1, "cell is outside of the map"),
+        ];
+
+        for (text, line, reason) in cases {
+            let e = parse_error(text);
+            assert_eq!((e.line, e.reason), (line, reason), "{:?}", text);
         }
     }
 }
@@ -0,0 +1,343 @@
+//! Procedural level generation.
+//!
+//! Levels are either rooms connected by corridors or cellular-automata caves. Generation is fully
+//! determined by the seed, so a level can be reproduced from its seed alone.
+
+use std::collections::VecDeque;
+
+use crate::map::{Map, Cell};
+use crate::player::Pos;
+use crate::rng::Rng;
+use crate::tile;
+
+const ROOM_ATTEMPTS: u32 = 200;
+const MAX_ROOMS: usize = 15;
+const CAVE_ATTEMPTS: u32 = 10;
+const CAVE_WALL_CHANCE: u32 = 45;
+const CAVE_SMOOTHING: u32 = 5;
+/// Percentage of the map that the main cave must cover, otherwise it's generated again.
+const CAVE_MIN_COVERAGE: usize = 30;
+/// Smallest size of a level: two floor cells next to each other with walls around them, one for
+/// each staircase.
+const MIN_HEIGHT: usize = 3;
+const MIN_WIDTH: usize = 4;
+
+/// Generates a level of `height` x `width` cells, or the minimum size if that's bigger. The player
+/// spawns on the up staircase, and the down staircase is placed as far away from it as possible.
+pub fn generate(seed: u64, height: usize, width: usize) -> Map
+{
+    let height = height.max(MIN_HEIGHT);
+    let width = width.max(MIN_WIDTH);
+    let mut rng = Rng::new(seed);
+
+    let mut map = Map::new(0, 0);
+    map.resize(height, width, tile::WALL);
+
+    let spawn = if rng.chance(70) {
+        map.meta.name = "Dungeon".to_string();
+        rooms_and_corridors(&mut map, &mut rng)
+    } else {
+        map.meta.name = "Caves".to_string();
+        match caves(&mut map, &mut rng) {
+            Some(spawn) => spawn,
+            None => {
+                map.resize(0, 0, tile::WALL);
+                map.resize(height, width, tile::WALL);
+                map.meta.name = "Dungeon".to_string();
+                rooms_and_corridors(&mut map, &mut rng)
+            },
+        }
+    };
+
+    let down = farthest_reachable(&map, spawn);
+    map.set_cell(spawn, tile::STAIRS_UP);
+    map.set_cell(down, tile::STAIRS_DOWN);
+    map.meta.spawn = spawn;
+    map.meta.author = format!("mapgen, seed {}", seed);
+
+    map
+}
+
+#[derive(Clone, Copy)]
+struct Room {
+    y: u32,
+    x: u32,
+    height: u32,
+    width: u32,
+}
+
+impl Room {
+    /// Whether the rooms overlap or touch, leaving no wall between them.
+    fn intersects(&self, other: &Room) -> bool
+    {
+        self.y <= other.y + other.height
+            && other.y <= self.y + self.height
+            && self.x <= other.x + other.width
+            && other.x <= self.x + self.width
+    }
+
+    fn centre(&self) -> Pos
+    {
+        Pos {
+            y: self.y + self.height / 2,
+            x: self.x + self.width / 2,
+        }
+    }
+
+    fn carve(&self, map: &mut Map)
+    {
+        for y in self.y..self.y + self.height {
+            for x in self.x..self.x + self.width {
+                map.set_cell(Pos { y, x }, tile::FLOOR);
+            }
+        }
+    }
+}
+
+/// Returns the spawn point. The map must be at least `MIN_HEIGHT` x `MIN_WIDTH`.
+fn rooms_and_corridors(map: &mut Map, rng: &mut Rng) -> Pos
+{
+    let height = map.height() as u32;
+    let width = map.width() as u32;
+    let mut rooms: Vec<Room> = Vec::new();
+
+    for _ in 0..ROOM_ATTEMPTS {
+        if rooms.len() == MAX_ROOMS {
+            break;
+        }
+
+        let room_height = rng.range(3, 8).min(height - 2);
+        let room_width = rng.range(4, 14).min(width - 2);
+        let room = Room {
+            y: rng.range(1, height - room_height),
+            x: rng.range(1, width - room_width),
+            height: room_height,
+            width: room_width,
+        };
+
+        if rooms.iter().any(|r| r.intersects(&room)) {
+            continue;
+        }
+
+        room.carve(map);
+
+        if let Some(prev) = rooms.last() {
+            corridor(map, rng, prev.centre(), room.centre());
+        }
+
+        rooms.push(room);
+    }
+
+    match rooms.first() {
+        Some(room) => room.centre(),
+        // Nothing fit, make the whole level a single room.
+        None => {
+            let room = Room { y: 1, x: 1, height: height - 2, width: width - 2 };
+            room.carve(map);
+            room.centre()
+        },
+    }
+}
+
+/// Carves an L-shaped corridor.
+fn corridor(map: &mut Map, rng: &mut Rng, from: Pos, to: Pos)
+{
+    let corner = if rng.chance(50) {
+        Pos { y: from.y, x: to.x }
+    } else {
+        Pos { y: to.y, x: from.x }
+    };
+
+    for (a, b) in [(from, corner), (corner, to)] {
+        for y in a.y.min(b.y)..=a.y.max(b.y) {
+            for x in a.x.min(b.x)..=a.x.max(b.x) {
+                let pos = Pos { y, x };
+                if map.cell(pos) == tile::WALL {
+                    map.set_cell(pos, tile::FLOOR);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the spawn point, or `None` if no attempt produced a big enough cave.
+fn caves(map: &mut Map, rng: &mut Rng) -> Option<Pos>
+{
+    let height = map.height();
+    let width = map.width();
+
+    for _ in 0..CAVE_ATTEMPTS {
+        for y in 0..height {
+            for x in 0..width {
+                let edge = y == 0 || x == 0 || y == height - 1 || x == width - 1;
+                let wall = edge || rng.chance(CAVE_WALL_CHANCE);
+                let cell = if wall { tile::WALL } else { tile::FLOOR };
+                map.set_cell(Pos { y: y as u32, x: x as u32 }, cell);
+            }
+        }
+
+        for _ in 0..CAVE_SMOOTHING {
+            smooth(map);
+        }
+
+        let cave = largest_region(map);
+        if cave.len() * 100 < height * width * CAVE_MIN_COVERAGE {
+            continue;
+        }
+
+        // Fill in everything that isn't connected to the main cave.
+        let mut in_cave = vec![false; height * width];
+        for pos in &cave {
+            in_cave[pos.y as usize * width + pos.x as usize] = true;
+        }
+        for y in 0..height {
+            for x in 0..width {
+                if !in_cave[y * width + x] {
+                    map.set_cell(Pos { y: y as u32, x: x as u32 }, tile::WALL);
+                }
+            }
+        }
+
+        return Some(cave[rng.range(0, cave.len() as u32) as usize]);
+    }
+
+    None
+}
+
+/// One cellular automaton step: a cell becomes a wall if at least 5 cells of its 3x3
+/// neighbourhood are walls. Cells outside of the map count as walls.
+fn smooth(map: &mut Map)
+{
+    let height = map.height() as i64;
+    let width = map.width() as i64;
+    let mut next: Vec<(Pos, Cell)> = Vec::with_capacity((height * width) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut walls = 0;
+            for ny in y - 1..=y + 1 {
+                for nx in x - 1..=x + 1 {
+                    let outside = ny < 0 || nx < 0 || ny >= height || nx >= width;
+                    if outside || map.cell(Pos { y: ny as u32, x: nx as u32 }) == tile::WALL {
+                        walls += 1;
+                    }
+                }
+            }
+
+            let cell = if walls >= 5 { tile::WALL } else { tile::FLOOR };
+            next.push((Pos { y: y as u32, x: x as u32 }, cell));
+        }
+    }
+
+    for (pos, cell) in next {
+        map.set_cell(pos, cell);
+    }
+}
+
+/// Passable neighbours of `pos`, in all 8 directions.
+fn neighbours(map: &Map, pos: Pos) -> impl Iterator<Item = Pos> + '_
+{
+    const OFFSETS: [(i64, i64); 8] = [
+        (-1, -1), (-1, 0), (-1, 1),
+        (0, -1), (0, 1),
+        (1, -1), (1, 0), (1, 1),
+    ];
+
+    OFFSETS.iter().filter_map(move |(dy, dx)| {
+        let y = pos.y as i64 + dy;
+        let x = pos.x as i64 + dx;
+        if y < 0 || x < 0 {
+            return None;
+        }
+
+        let n = Pos { y: y as u32, x: x as u32 };
+        if map.in_bounds(n) && map.cell(n).props().passable { Some(n) } else { None }
+    })
+}
+
+/// Breadth-first search from `start`. Returns the reached positions in the order they were
+/// reached, so the last one is the farthest.
+fn flood(map: &Map, start: Pos, seen: &mut [bool]) -> Vec<Pos>
+{
+    let width = map.width();
+    let mut reached = Vec::new();
+    let mut queue = VecDeque::new();
+
+    seen[start.y as usize * width + start.x as usize] = true;
+    queue.push_back(start);
+
+    while let Some(pos) = queue.pop_front() {
+        reached.push(pos);
+
+        for n in neighbours(map, pos) {
+            let i = n.y as usize * width + n.x as usize;
+            if !seen[i] {
+                seen[i] = true;
+                queue.push_back(n);
+            }
+        }
+    }
+
+    reached
+}
+
+fn largest_region(map: &Map) -> Vec<Pos>
+{
+    let mut seen = vec![false; map.height() * map.width()];
+    let mut largest = Vec::new();
+
+    for y in 0..map.height() {
+        for x in 0..map.width() {
+            let pos = Pos { y: y as u32, x: x as u32 };
+            if seen[y * map.width() + x] || !map.cell(pos).props().passable {
+                continue;
+            }
+
+            let region = flood(map, pos, &mut seen);
+            if region.len() > largest.len() {
+                largest = region;
+            }
+        }
+    }
+
+    largest
+}
+
+fn farthest_reachable(map: &Map, start: Pos) -> Pos
+{
+    let mut seen = vec![false; map.height() * map.width()];
+
+    *flood(map, start, &mut seen).last().unwrap_or(&start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_map()
+    {
+        for seed in 0..20 {
+            let a = generate(seed, 40, 80);
+            let b = generate(seed, 40, 80);
+
+            assert_eq!(a.grid, b.grid, "seed {}", seed);
+            assert_eq!(a.meta.spawn, b.meta.spawn, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn tiny_maps()
+    {
+        for (height, width) in [(0, 0), (1, 1), (2, 5), (3, 3), (3, 4), (4, 100)] {
+            for seed in 0..20 {
+                let map = generate(seed, height, width);
+                let what = format!("{}x{}, seed {}", height, width, seed);
+
+                assert!(map.height() >= MIN_HEIGHT && map.width() >= MIN_WIDTH, "{}", what);
+                assert_eq!(map.find(tile::STAIRS_UP), Some(map.meta.spawn), "{}", what);
+                assert!(map.find(tile::STAIRS_DOWN).is_some(), "{}", what);
+            }
+        }
+    }
+}
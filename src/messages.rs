@@ -0,0 +1,94 @@
+//! The message log: what happened in the game, as told to the player.
+
+use crate::tile::RgbValue;
+
+/// Number of messages kept in the history.
+const HISTORY_LEN: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    /// Something good happened to the player.
+    Good,
+    Warning,
+    /// The player is in danger, e.g. being hit.
+    Danger,
+}
+
+impl Severity {
+    pub fn color(self) -> RgbValue
+    {
+        match self {
+            Severity::Info => (0xd0, 0xd0, 0xd0),
+            Severity::Good => (0x60, 0xd0, 0x60),
+            Severity::Warning => (0xe0, 0xc0, 0x40),
+            Severity::Danger => (0xff, 0x50, 0x50),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub text: String,
+    pub severity: Severity,
+    /// Game turn when the message was logged.
+    pub turn: u64,
+}
+
+#[derive(Default)]
+pub struct MessageLog {
+    messages: Vec<Message>,
+    /// Number of messages at the end that haven't been shown yet.
+    unread: usize,
+    turn: u64,
+}
+
+impl MessageLog {
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Sets the turn that the following messages are logged at.
+    pub fn set_turn(&mut self, turn: u64)
+    {
+        self.turn = turn;
+    }
+
+    pub fn log<S: Into<String>>(&mut self, text: S)
+    {
+        self.log_as(Severity::Info, text);
+    }
+
+    pub fn log_as<S: Into<String>>(&mut self, severity: Severity, text: S)
+    {
+        self.messages.push(Message {
+            text: text.into(),
+            severity,
+            turn: self.turn,
+        });
+        self.unread = (self.unread + 1).min(HISTORY_LEN);
+
+        if self.messages.len() > HISTORY_LEN {
+            let excess = self.messages.len() - HISTORY_LEN;
+            self.messages.drain(..excess);
+        }
+    }
+
+    /// Messages logged since the last call to `mark_read`, oldest first.
+    pub fn unread(&self) -> &[Message]
+    {
+        &self.messages[self.messages.len() - self.unread..]
+    }
+
+    pub fn mark_read(&mut self)
+    {
+        self.unread = 0;
+    }
+
+    /// All of the kept messages, oldest first.
+    pub fn history(&self) -> &[Message]
+    {
+        &self.messages
+    }
+}
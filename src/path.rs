@@ -0,0 +1,213 @@
+//! Pathfinding on the map grid.
+//!
+//! Steps go in the 8 directions of `Direction`, and cost whatever the callers' cost function says,
+//! normally the `move_cost` of the tile. Ties are broken by the order in which cells are reached,
+//! so the same map always gives the same path.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use crate::direction::Direction;
+use crate::map::Map;
+use crate::player::Pos;
+use crate::turn::ACTION_COST;
+
+/// Neighbours of `pos` that are inside of the map, with the cost of stepping onto them.
+/// Positions for which `cost` returns `None` can't be entered.
+fn neighbours<'a, F>(map: &'a Map, pos: Pos, cost: &'a F) -> impl Iterator<Item = (Pos, u32)> + 'a
+where
+    F: Fn(Pos) -> Option<u32>,
+{
+    Direction::ALL.iter().filter_map(move |dir| {
+        let n = dir.step(pos).filter(|n| map.in_bounds(*n))?;
+
+        Some((n, cost(n)?))
+    })
+}
+
+fn index(map: &Map, pos: Pos) -> usize
+{
+    pos.y as usize * map.width() + pos.x as usize
+}
+
+/// Cheapest path from `from` to `to` using A*, not including `from` itself. Returns `None` if
+/// `to` can't be reached.
+///
+/// `cost` must not return less than `ACTION_COST` for a step, otherwise the path might not be
+/// the cheapest one.
+pub fn find<F>(map: &Map, from: Pos, to: Pos, cost: F) -> Option<Vec<Pos>>
+where
+    F: Fn(Pos) -> Option<u32>,
+{
+    if !map.in_bounds(from) || !map.in_bounds(to) {
+        return None;
+    }
+    if from == to {
+        return Some(Vec::new());
+    }
+
+    // Diagonal steps cost the same as straight ones, so the Chebyshev distance never
+    // overestimates.
+    let heuristic = |pos: Pos| pos.y.abs_diff(to.y).max(pos.x.abs_diff(to.x)) * ACTION_COST;
+
+    let mut best = vec![u32::MAX; map.height() * map.width()];
+    let mut came_from: Vec<Option<Pos>> = vec![None; map.height() * map.width()];
+    let mut open = BinaryHeap::new();
+    let mut order = 0u32;
+
+    best[index(map, from)] = 0;
+    open.push(Reverse((heuristic(from), order, from.y, from.x)));
+
+    while let Some(Reverse((_, _, y, x))) = open.pop() {
+        let pos = Pos { y, x };
+        if pos == to {
+            let mut path = vec![to];
+            let mut at = to;
+            while let Some(prev) = came_from[index(map, at)] {
+                if prev == from {
+                    break;
+                }
+                path.push(prev);
+                at = prev;
+            }
+            path.reverse();
+
+            return Some(path);
+        }
+
+        let so_far = best[index(map, pos)];
+
+        for (n, step) in neighbours(map, pos, &cost) {
+            let total = so_far + step;
+            let i = index(map, n);
+
+            if total < best[i] {
+                best[i] = total;
+                came_from[i] = Some(pos);
+                order += 1;
+                open.push(Reverse((total + heuristic(n), order, n.y, n.x)));
+            }
+        }
+    }
+
+    None
+}
+
+/// Cost of the cheapest path from `from` to every position of the map, using Dijkstra's
+/// algorithm. Unreachable positions are `None`. Indexed row-major, like the map grid.
+pub fn distances<F>(map: &Map, from: Pos, cost: F) -> Vec<Option<u32>>
+where
+    F: Fn(Pos) -> Option<u32>,
+{
+    let mut dist = vec![None; map.height() * map.width()];
+    if !map.in_bounds(from) {
+        return dist;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut order = 0u32;
+
+    dist[index(map, from)] = Some(0);
+    open.push(Reverse((0, order, from.y, from.x)));
+
+    while let Some(Reverse((so_far, _, y, x))) = open.pop() {
+        let pos = Pos { y, x };
+        if dist[index(map, pos)].is_some_and(|d| d < so_far) {
+            continue;
+        }
+
+        for (n, step) in neighbours(map, pos, &cost) {
+            let total = so_far + step;
+            let i = index(map, n);
+
+            if dist[i].is_none_or(|d| total < d) {
+                dist[i] = Some(total);
+                order += 1;
+                open.push(Reverse((total, order, n.y, n.x)));
+            }
+        }
+    }
+
+    dist
+}
+
+/// Distance at `pos` in a result of `distances`.
+pub fn distance_at(map: &Map, distances: &[Option<u32>], pos: Pos) -> Option<u32>
+{
+    if !map.in_bounds(pos) {
+        return None;
+    }
+
+    distances[index(map, pos)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(rows: &str) -> Map
+    {
+        Map::from_text(&format!("---\n{}", rows)).unwrap()
+    }
+
+    fn cost(map: &Map) -> impl Fn(Pos) -> Option<u32> + '_
+    {
+        |pos| map.cell(pos).props().passable.then_some(ACTION_COST)
+    }
+
+    #[test]
+    fn diagonal_steps()
+    {
+        let map = map("......\n......\n......\n......\n");
+        let from = Pos { y: 0, x: 0 };
+        let to = Pos { y: 3, x: 5 };
+        let path = find(&map, from, to, cost(&map)).unwrap();
+
+        assert_eq!(path.len(), 5);
+        assert_eq!(path.last(), Some(&to));
+
+        let dist = distances(&map, from, cost(&map));
+        assert_eq!(distance_at(&map, &dist, to), Some(5 * ACTION_COST));
+    }
+
+    #[test]
+    fn walls_block()
+    {
+        let map = map(".#...\n.#.#.\n...#.\n");
+        let from = Pos { y: 0, x: 0 };
+        let to = Pos { y: 0, x: 4 };
+        let path = find(&map, from, to, cost(&map)).unwrap();
+
+        // Down and around the first wall, then diagonally past the second one.
+        assert_eq!(path.len(), 5);
+        assert!(path.iter().all(|p| map.cell(*p).props().passable));
+        let mut steps = std::iter::once(&from).chain(&path).zip(&path);
+        assert!(steps.all(|(a, b)| a.y.abs_diff(b.y).max(a.x.abs_diff(b.x)) == 1));
+
+        let dist = distances(&map, from, cost(&map));
+        assert_eq!(distance_at(&map, &dist, to), Some(5 * ACTION_COST));
+        assert_eq!(distance_at(&map, &dist, Pos { y: 0, x: 1 }), None);
+    }
+
+    #[test]
+    fn unreachable()
+    {
+        let map = map("..#..\n..#..\n");
+        let from = Pos { y: 0, x: 0 };
+        let to = Pos { y: 1, x: 4 };
+
+        assert_eq!(find(&map, from, to, cost(&map)), None);
+        assert_eq!(distance_at(&map, &distances(&map, from, cost(&map)), to), None);
+        assert_eq!(find(&map, from, Pos { y: 5, x: 0 }, cost(&map)), None);
+    }
+
+    #[test]
+    fn same_position()
+    {
+        let map = map("...\n");
+        let pos = Pos { y: 0, x: 1 };
+
+        assert_eq!(find(&map, pos, pos, cost(&map)), Some(Vec::new()));
+        assert_eq!(distance_at(&map, &distances(&map, pos, cost(&map)), pos), Some(0));
+    }
+}
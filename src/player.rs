@@ -1,20 +1,150 @@
-#[derive(Clone, Copy)]
+use crate::turn::{Actor, NORMAL_SPEED};
+use crate::combat::Stats;
+use crate::item::{self, Inventory, MAX_WEIGHT};
+use crate::character::{Race, Class, Attributes};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct Pos {
     pub y: u32,
     pub x: u32,
 }
 
+/// Lasting conditions of the player, that follow from the player's state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// Carrying more than 3/4 of the maximum weight. Moving takes longer.
+    Burdened,
+    /// Down to 1/4 of the maximum HP or less.
+    Wounded,
+}
+
+impl Condition {
+    pub fn name(self) -> &'static str
+    {
+        match self {
+            Condition::Burdened => "Burdened",
+            Condition::Wounded => "Wounded",
+        }
+    }
+}
+
 pub struct Player {
     pub name: String,
+    pub race: Race,
+    pub class: Class,
+    /// Attributes, including the race modifiers.
+    pub attributes: Attributes,
     pub pos: Pos,
+    pub actor: Actor,
+    /// Stats without any equipment.
+    pub stats: Stats,
+    pub inventory: Inventory,
+    pub level: u32,
+    /// Experience gathered towards the next level.
+    pub xp: u32,
 }
 
 impl Player {
     pub fn new() -> Self
     {
+        let attributes = Attributes {
+            strength: 10,
+            dexterity: 10,
+            constitution: 10,
+        };
+
         Self {
             name: String::new(),
+            race: Race::Human,
+            class: Class::Warrior,
+            attributes,
             pos: Pos {y: 0, x: 0},
+            actor: Actor::new(NORMAL_SPEED),
+            stats: attributes.stats(Class::Warrior),
+            inventory: Inventory::new(),
+            level: 1,
+            xp: 0,
+        }
+    }
+
+    /// A new character, with the stats and the equipment that follow from the choices made in
+    /// character creation.
+    pub fn create(name: String, race: Race, class: Class, attributes: Attributes) -> Self
+    {
+        let mut player = Self::new();
+        player.name = name;
+        player.race = race;
+        player.class = class;
+        player.attributes = attributes;
+        player.stats = attributes.stats(class);
+
+        for id in class.starting_items() {
+            let item = match item::by_id(*id) {
+                Some(item) => item,
+                None => continue,
+            };
+            let slot = item.slot();
+
+            if player.inventory.add(item).is_err() {
+                continue;
+            }
+            if slot.is_some_and(|s| player.inventory.equipped(s).is_none()) {
+                player.inventory.equip(player.inventory.pack.len() - 1);
+            }
+        }
+
+        player
+    }
+
+    /// Experience needed to reach the next level.
+    pub fn xp_needed(&self) -> u32
+    {
+        self.level * 20
+    }
+
+    /// Adds experience, and goes up as many levels as there's enough of it for. Returns `true` if
+    /// the player went up a level.
+    pub fn gain_xp(&mut self, xp: u32) -> bool
+    {
+        let old_level = self.level;
+
+        self.xp += xp;
+        while self.xp >= self.xp_needed() {
+            self.xp -= self.xp_needed();
+            self.level += 1;
+            self.stats.max_hp += 5;
+            self.stats.hp += 5;
+            self.stats.attack += 1;
+            self.stats.accuracy += 2;
+        }
+
+        self.level != old_level
+    }
+
+    pub fn conditions(&self) -> Vec<Condition>
+    {
+        let mut conditions = Vec::new();
+
+        if self.inventory.weight() * 4 > MAX_WEIGHT * 3 {
+            conditions.push(Condition::Burdened);
+        }
+        if self.stats.hp * 4 <= self.stats.max_hp {
+            conditions.push(Condition::Wounded);
+        }
+
+        conditions
+    }
+
+    /// Stats with the bonuses of the equipment.
+    pub fn combat_stats(&self) -> Stats
+    {
+        let (attack, defense, accuracy) = self.inventory.bonuses();
+
+        Stats {
+            attack: self.stats.attack + attack,
+            defense: self.stats.defense + defense,
+            accuracy: self.stats.accuracy + accuracy,
+            ..self.stats
         }
     }
 }
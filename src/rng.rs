@@ -0,0 +1,50 @@
+//! Small deterministic random number generator.
+//!
+//! The same seed always produces the same sequence on every platform, which is what makes
+//! generated levels and game behaviour reproducible.
+
+/// SplitMix64.
+#[derive(Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self
+    {
+        Self { state: seed }
+    }
+
+    /// Current state. `Rng::new(rng.state())` continues the same sequence.
+    pub fn state(&self) -> u64
+    {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64
+    {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `low..high`. Returns `low` for empty ranges.
+    pub fn range(&mut self, low: u32, high: u32) -> u32
+    {
+        if high <= low {
+            return low;
+        }
+
+        low + (self.next_u64() % (high - low) as u64) as u32
+    }
+
+    /// `true` with the given probability, in percent.
+    pub fn chance(&mut self, percent: u32) -> bool
+    {
+        self.range(0, 100) < percent
+    }
+}
@@ -0,0 +1,298 @@
+//! Sectioned binary container shared by the on-disk formats.
+//!
+//! Layout (all integers are little-endian):
+//!
+//! ```text
+//! magic          [u8; 4]
+//! version        u32
+//! section count  u32
+//! sections       { tag: [u8; 4], length: u32, payload: [u8; length] } * count
+//! checksum       u32   FNV-1a of every preceding byte
+//! ```
+//!
+//! Readers look sections up by tag and skip the ones they don't know, so new sections can be
+//! added without breaking older files.
+
+use std::io::{Read, Write};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u32),
+    BadChecksum,
+    MissingSection([u8; 4]),
+    Corrupt(&'static str),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
+    {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Truncated => write!(f, "unexpected end of file"),
+            Error::BadMagic => write!(f, "not a recognised file"),
+            Error::UnsupportedVersion(v) => write!(f, "unsupported format version: {}", v),
+            Error::BadChecksum => write!(f, "checksum mismatch"),
+            Error::MissingSection(tag) => {
+                write!(f, "missing section: {}", String::from_utf8_lossy(tag))
+            },
+            Error::Corrupt(what) => write!(f, "corrupt data: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self
+    {
+        if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            Error::Truncated
+        } else {
+            Error::Io(e)
+        }
+    }
+}
+
+/// Writes `bytes` to `path` with `.tmp` appended first and then moves that file in place, so that
+/// a failed write doesn't destroy the old file.
+pub fn write_file(path: &Path, bytes: &[u8]) -> Result<(), Error>
+{
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut f = std::fs::File::create(&tmp_path)?;
+    f.write_all(bytes)?;
+    f.sync_all()?;
+    std::fs::rename(tmp_path, path)?;
+
+    Ok(())
+}
+
+/// 32-bit FNV-1a.
+pub fn checksum(data: &[u8]) -> u32
+{
+    let mut hash: u32 = 0x811c9dc5;
+    for b in data {
+        hash ^= *b as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+
+    hash
+}
+
+#[derive(Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    pub fn put_u8(&mut self, v: u8)
+    {
+        self.buf.push(v);
+    }
+
+    pub fn put_bool(&mut self, v: bool)
+    {
+        self.put_u8(v as u8);
+    }
+
+    pub fn put_u32(&mut self, v: u32)
+    {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn put_i32(&mut self, v: i32)
+    {
+        self.put_u32(v as u32);
+    }
+
+    pub fn put_u64(&mut self, v: u64)
+    {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+    }
+
+    pub fn put_bytes(&mut self, v: &[u8])
+    {
+        self.buf.extend_from_slice(v);
+    }
+
+    /// Length-prefixed UTF-8.
+    pub fn put_str(&mut self, v: &str)
+    {
+        self.put_u32(v.len() as u32);
+        self.put_bytes(v.as_bytes());
+    }
+}
+
+pub struct Decoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self
+    {
+        Self { data }
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        self.data.is_empty()
+    }
+
+    pub fn get_bytes(&mut self, len: usize) -> Result<&'a [u8], Error>
+    {
+        if self.data.len() < len {
+            return Err(Error::Truncated);
+        }
+
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+
+        Ok(head)
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8, Error>
+    {
+        Ok(self.get_bytes(1)?[0])
+    }
+
+    pub fn get_bool(&mut self) -> Result<bool, Error>
+    {
+        match self.get_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::Corrupt("invalid boolean")),
+        }
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32, Error>
+    {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.get_bytes(4)?);
+
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    pub fn get_i32(&mut self) -> Result<i32, Error>
+    {
+        Ok(self.get_u32()? as i32)
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64, Error>
+    {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.get_bytes(8)?);
+
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    pub fn get_str(&mut self) -> Result<String, Error>
+    {
+        let len = self.get_u32()? as usize;
+        let bytes = self.get_bytes(len)?;
+
+        String::from_utf8(bytes.to_vec()).map_err(|_| Error::Corrupt("invalid UTF-8 string"))
+    }
+}
+
+pub struct Container {
+    pub version: u32,
+    sections: Vec<([u8; 4], Vec<u8>)>,
+}
+
+impl Container {
+    pub fn new(version: u32) -> Self
+    {
+        Self {
+            version,
+            sections: Vec::new(),
+        }
+    }
+
+    pub fn add_section(&mut self, tag: [u8; 4], payload: Encoder)
+    {
+        self.sections.push((tag, payload.buf));
+    }
+
+    pub fn section(&self, tag: [u8; 4]) -> Option<Decoder<'_>>
+    {
+        self.sections.iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, data)| Decoder::new(data))
+    }
+
+    pub fn require_section(&self, tag: [u8; 4]) -> Result<Decoder<'_>, Error>
+    {
+        self.section(tag).ok_or(Error::MissingSection(tag))
+    }
+
+    pub fn write<W: Write>(&self, magic: [u8; 4], writer: &mut W) -> Result<(), Error>
+    {
+        let mut enc = Encoder::new();
+        enc.put_bytes(&magic);
+        enc.put_u32(self.version);
+        enc.put_u32(self.sections.len() as u32);
+        for (tag, data) in &self.sections {
+            enc.put_bytes(tag);
+            enc.put_u32(data.len() as u32);
+            enc.put_bytes(data);
+        }
+        let sum = checksum(&enc.buf);
+        enc.put_u32(sum);
+
+        writer.write_all(&enc.buf)?;
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(magic: [u8; 4], reader: &mut R) -> Result<Self, Error>
+    {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        Self::from_bytes(magic, &bytes)
+    }
+
+    pub fn from_bytes(magic: [u8; 4], bytes: &[u8]) -> Result<Self, Error>
+    {
+        if bytes.len() < 4 || bytes[..4] != magic {
+            return Err(Error::BadMagic);
+        }
+        if bytes.len() < 4 + 4 + 4 + 4 {
+            return Err(Error::Truncated);
+        }
+
+        let (body, sum) = bytes.split_at(bytes.len() - 4);
+        let mut dec = Decoder::new(sum);
+        let stored = dec.get_u32()?;
+
+        let mut dec = Decoder::new(&body[4..]);
+        let version = dec.get_u32()?;
+        let count = dec.get_u32()?;
+
+        let mut sections = Vec::new();
+        for _ in 0..count {
+            let mut tag = [0; 4];
+            tag.copy_from_slice(dec.get_bytes(4)?);
+            let len = dec.get_u32()? as usize;
+            sections.push((tag, dec.get_bytes(len)?.to_vec()));
+        }
+
+        if !dec.is_empty() {
+            return Err(Error::Corrupt("trailing data after the last section"));
+        }
+        if checksum(body) != stored {
+            return Err(Error::BadChecksum);
+        }
+
+        Ok(Self { version, sections })
+    }
+}
@@ -0,0 +1,172 @@
+//! Tile types: what each `Cell` value means.
+
+use crate::map::Cell;
+
+pub type RgbValue = (u8, u8, u8);
+
+/// What happens when something tries to move onto an impassable tile.
+pub enum Bump {
+    Block,
+    /// The tile turns into another one, e.g. a door opens.
+    Open(Cell),
+    Interact(&'static str),
+}
+
+pub struct TileProps {
+    pub cell: Cell,
+    pub name: &'static str,
+    pub glyph: char,
+    pub color: RgbValue,
+    pub passable: bool,
+    pub opaque: bool,
+    /// Cost of stepping onto the tile, 100 being a normal step.
+    pub move_cost: u32,
+    pub bump: Bump,
+    pub description: &'static str,
+}
+
+pub const VOID: Cell = Cell(b' ');
+pub const WALL: Cell = Cell(b'#');
+pub const FLOOR: Cell = Cell(b'.');
+pub const DOOR_CLOSED: Cell = Cell(b'+');
+pub const DOOR_OPEN: Cell = Cell(b'\'');
+pub const WATER: Cell = Cell(b'~');
+pub const FOUNTAIN: Cell = Cell(b'{');
+pub const STAIRS_UP: Cell = Cell(b'<');
+pub const STAIRS_DOWN: Cell = Cell(b'>');
+
+pub static TILES: [TileProps; 9] = [
+    TileProps {
+        cell: VOID,
+        name: "void",
+        glyph: ' ',
+        color: (0x00, 0x00, 0x00),
+        passable: false,
+        opaque: false,
+        move_cost: 100,
+        bump: Bump::Block,
+        description: "Nothing but darkness.",
+    },
+    TileProps {
+        cell: WALL,
+        name: "wall",
+        glyph: '#',
+        color: (0x9e, 0x9e, 0x9e),
+        passable: false,
+        opaque: true,
+        move_cost: 100,
+        bump: Bump::Block,
+        description: "A rough stone wall.",
+    },
+    TileProps {
+        cell: FLOOR,
+        name: "floor",
+        glyph: '.',
+        color: (0x70, 0x70, 0x70),
+        passable: true,
+        opaque: false,
+        move_cost: 100,
+        bump: Bump::Block,
+        description: "A stone floor.",
+    },
+    TileProps {
+        cell: DOOR_CLOSED,
+        name: "closed door",
+        glyph: '+',
+        color: (0xc0, 0x8a, 0x4a),
+        passable: false,
+        opaque: true,
+        move_cost: 100,
+        bump: Bump::Open(DOOR_OPEN),
+        description: "A closed wooden door.",
+    },
+    TileProps {
+        cell: DOOR_OPEN,
+        name: "open door",
+        glyph: '\'',
+        color: (0xc0, 0x8a, 0x4a),
+        passable: true,
+        opaque: false,
+        move_cost: 100,
+        bump: Bump::Block,
+        description: "An open wooden door.",
+    },
+    TileProps {
+        cell: WATER,
+        name: "water",
+        glyph: '~',
+        color: (0x3a, 0x7b, 0xd5),
+        passable: true,
+        opaque: false,
+        move_cost: 200,
+        bump: Bump::Block,
+        description: "Shallow water. Wading through it is slow.",
+    },
+    TileProps {
+        cell: FOUNTAIN,
+        name: "fountain",
+        glyph: '{',
+        color: (0x6f, 0xc3, 0xdf),
+        passable: false,
+        opaque: false,
+        move_cost: 100,
+        bump: Bump::Interact("You drink from the fountain. The water is cold and clear."),
+        description: "A stone fountain.",
+    },
+    TileProps {
+        cell: STAIRS_UP,
+        name: "staircase up",
+        glyph: '<',
+        color: (0xf0, 0xe0, 0x80),
+        passable: true,
+        opaque: false,
+        move_cost: 100,
+        bump: Bump::Block,
+        description: "A staircase leading up.",
+    },
+    TileProps {
+        cell: STAIRS_DOWN,
+        name: "staircase down",
+        glyph: '>',
+        color: (0xf0, 0xe0, 0x80),
+        passable: true,
+        opaque: false,
+        move_cost: 100,
+        bump: Bump::Block,
+        description: "A staircase leading down.",
+    },
+];
+
+/// Used for cell values that aren't in `TILES`. Older maps use all sorts of glyphs for floors and
+/// decorations, so these can be walked on rather than walling the player in.
+static UNKNOWN: TileProps = TileProps {
+    cell: Cell(0),
+    name: "unknown",
+    glyph: '?',
+    color: (0xff, 0x00, 0xff),
+    passable: true,
+    opaque: false,
+    move_cost: 100,
+    bump: Bump::Block,
+    description: "Something strange.",
+};
+
+impl Cell {
+    pub fn props(self) -> &'static TileProps
+    {
+        TILES.iter()
+            .find(|t| t.cell == self)
+            .unwrap_or(&UNKNOWN)
+    }
+
+    /// Glyph to draw the cell with. Unknown cells are drawn as their raw value if it's printable
+    /// ASCII, anything else could mess up the terminal.
+    pub fn glyph(self) -> char
+    {
+        match TILES.iter().find(|t| t.cell == self) {
+            Some(t) => t.glyph,
+            None if self.0.is_ascii_graphic() => self.0 as char,
+            None => UNKNOWN.glyph,
+        }
+    }
+}
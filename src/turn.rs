@@ -0,0 +1,40 @@
+//! Energy based turn scheduling.
+//!
+//! Every actor gains energy equal to its speed on each tick, and can act once it has at least
+//! `ACTION_COST` of it. Acting spends energy according to the cost of the action, so fast actors
+//! act more often and slow actions (like wading through water) delay the actor's next turn.
+
+/// Energy needed to act, and the cost of an ordinary action.
+pub const ACTION_COST: u32 = 100;
+pub const NORMAL_SPEED: u32 = 100;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Actor {
+    pub speed: u32,
+    pub energy: i32,
+}
+
+impl Actor {
+    pub fn new(speed: u32) -> Self
+    {
+        Self {
+            speed,
+            energy: 0,
+        }
+    }
+
+    pub fn can_act(&self) -> bool
+    {
+        self.energy >= ACTION_COST as i32
+    }
+
+    pub fn tick(&mut self)
+    {
+        self.energy += self.speed as i32;
+    }
+
+    pub fn spend(&mut self, cost: u32)
+    {
+        self.energy -= cost as i32;
+    }
+}